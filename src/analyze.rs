@@ -0,0 +1,268 @@
+// `analyze` subcommand, what a rule file will produce before running it
+//
+// Static bounds come from the functions alone: the longest candidate as a
+// function of the word length n, and whether the line can reject words. A
+// sample of the wordlist then gives the real numbers: rejected words,
+// candidates an earlier rule line already produced for the same word (a run
+// drops those), and the distinct candidates over all words, estimated with
+// HyperLogLog.
+
+use std::collections::HashSet;
+use std::fmt;
+
+use crate::hyperloglog::HyperLogLog;
+use crate::random::Rng;
+use crate::rule::{self, Rule};
+use crate::source::{self, Line, Options, Shared};
+
+/// Upper bound of the candidate length, `mul * n + add`, at most `cap`.
+/// The terms saturate, a bound that overflows for a long word is unbounded.
+#[derive(Clone, Copy)]
+struct Growth {
+    mul: usize,
+    add: i64,
+    cap: Option<usize>,
+}
+
+impl Growth {
+    const WORD: Growth = Growth { mul: 1, add: 0, cap: None };
+
+    fn times(self, k: usize) -> Growth {
+        Growth {
+            mul: self.mul.saturating_mul(k),
+            add: self.add.saturating_mul(k as i64),
+            cap: self.cap.map(|c| c.saturating_mul(k)),
+        }
+    }
+
+    fn plus(self, k: i64) -> Growth {
+        Growth { add: self.add.saturating_add(k), cap: self.cap.map(|c| c.saturating_add_signed(k as isize)), ..self }
+    }
+
+    fn at_most(self, k: usize) -> Growth {
+        Growth { cap: Some(self.cap.map_or(k, |c| c.min(k))), ..self }
+    }
+
+    fn sum(self, other: Growth) -> Growth {
+        Growth {
+            mul: self.mul.saturating_add(other.mul),
+            add: self.add.saturating_add(other.add),
+            cap: self.cap.zip(other.cap).map(|(a, b)| a.saturating_add(b)),
+        }
+    }
+
+    /// The bound for a long word, to pick the largest of several
+    fn value(&self) -> i64 {
+        let long = i64::try_from(self.mul.saturating_mul(rule::MAX_LENGTH)).unwrap_or(i64::MAX).saturating_add(self.add);
+        self.cap.map_or(long, |c| long.min(i64::try_from(c).unwrap_or(i64::MAX)))
+    }
+}
+
+impl fmt::Display for Growth {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.value() == i64::MAX {
+            return write!(f, "unbounded");
+        }
+        let term = match (self.mul, self.add) {
+            (0, add) => add.max(0).to_string(),
+            (1, 0) => "n".to_string(),
+            (1, add) => format!("n{:+}", add),
+            (mul, 0) => format!("{}n", mul),
+            (mul, add) => format!("{}n{:+}", mul, add),
+        };
+        match self.cap {
+            Some(cap) if self.mul == 0 => write!(f, "{}", (self.add.max(0) as usize).min(cap)),
+            Some(cap) => write!(f, "min({}, {})", term, cap),
+            None => write!(f, "{}", term),
+        }
+    }
+}
+
+/// Static bounds of a rule line: the length bound, None if it can't be
+/// told, and whether it may reject words
+fn bounds(line: &[Rule]) -> (Option<Growth>, bool) {
+    let mut growth = Growth::WORD;
+    let mut memory = Growth::WORD;
+    let mut rejects = false;
+    for rule in line {
+        // Positions of JtR's variables are only known when applied
+        let rule = match rule {
+            Rule::Dynamic(inner, _) => match inner.as_ref() {
+                Rule::ExtractRange(_, _) | Rule::TruncateAt(_) | Rule::OmitRange(_, _) => continue,
+                Rule::ExtractMemory(_, _, _) | Rule::DuplicateN(_) | Rule::DuplicateFirstN(_) | Rule::DuplicateLastN(_) => return (None, true),
+                inner => inner
+            },
+            rule => rule
+        };
+        growth = match rule {
+            Rule::Append(_) | Rule::Prepend(_) | Rule::InsertAt(_, _) => growth.plus(1),
+            Rule::TruncateLeft() | Rule::TruncateRight() => growth.plus(-1),
+            Rule::Duplicate() | Rule::Reflect() | Rule::DuplicateAll() => growth.times(2),
+            Rule::DuplicateN(n) => growth.times(*n),
+            Rule::DuplicateFirstN(n) | Rule::DuplicateLastN(n) => growth.plus(*n as i64),
+            Rule::TruncateAt(n) => growth.at_most(*n),
+            Rule::ExtractRange(_, n) => growth.at_most(*n),
+            Rule::InsertString(_, s) => growth.plus(s.chars().count() as i64),
+            // `y` to `ies`
+            Rule::Pluralize() => growth.plus(2),
+            Rule::ExtractMemory(_, n, _) => growth.plus(*n as i64),
+            Rule::AppendMemory() | Rule::PrependMemory() => growth.sum(memory),
+            Rule::Memorize() => {
+                memory = growth;
+                growth
+            },
+            Rule::RejectLonger(n) | Rule::RejectNotLength(n) => {
+                rejects = true;
+                growth.at_most(*n)
+            },
+            Rule::RejectShorter(_) | Rule::RejectContains(_) | Rule::RejectNotContains(_) | Rule::RejectNotFirst(_)
+            | Rule::RejectNotLast(_) | Rule::RejectNotAt(_, _) | Rule::RejectFewer(_, _) | Rule::RejectUnchanged() => {
+                rejects = true;
+                growth
+            },
+            _ => growth
+        };
+    }
+    (Some(growth), rejects)
+}
+
+/// Sample results of a line of the rule file
+#[derive(Default)]
+struct Counts {
+    candidates: u64,
+    rejected: u64,
+    duplicates: u64,
+}
+
+/// Options shared with the other subcommands
+const SHARED: &[Shared] = &[Shared::Wordlist, Shared::Section];
+
+fn print_usage(program: &str) {
+    eprintln!("Usage: {} analyze [options] <rule file>...", program);
+    eprintln!("Prints the length bound of the candidates of every rule line and whether it may reject words,");
+    eprintln!("and with -w the candidates, rejected words and duplicates over a sample of the wordlist.");
+    eprintln!("Options:");
+    eprintln!("  -w <path to wordlist>         Wordlist to sample.");
+    eprintln!("  --sample <n>                  Number of words to sample (default 10000).");
+    eprintln!("  --seed <n>                    Seed of the sample (default 0).");
+    source::print_common_usage(SHARED);
+}
+
+pub fn main(program: &str, args: &[String]) {
+    let mut sample_size: usize = 10000;
+    let mut seed: u64 = 0;
+
+    let Options { paths, mut wordlists, section, .. } =
+        source::parse_args(program, args, print_usage, SHARED, |arg, args| {
+            match arg {
+                "--sample" => sample_size = args.number(arg) as usize,
+                "--seed" => seed = args.number(arg),
+                _ => return false
+            }
+            true
+        });
+    let wordlist_path = wordlists.pop();
+
+    if paths.is_empty() {
+        print_usage(program);
+        std::process::exit(1);
+    }
+
+    let lines = source::read_files(&paths, section.as_deref());
+    let rule_lines: usize = lines.iter().map(|l| l.rules.len()).sum();
+
+    let words = wordlist_path.map(|path| crate::read_words(Some(&path)));
+    let sample: Vec<&String> = match &words {
+        Some(words) if words.len() > sample_size => {
+            let mut selected = vec![false; words.len()];
+            Rng::new(seed).sample(sample_size, &mut selected);
+            words.iter().zip(selected).filter(|(_, s)| *s).map(|(w, _)| w).collect()
+        },
+        Some(words) => words.iter().collect(),
+        None => Vec::new()
+    };
+    let (counts, unique) = apply(&lines, &sample);
+
+    let mut header = "source\trule\tmax length\tmay reject".to_string();
+    if words.is_some() {
+        header.push_str("\tcandidates\trejected\tduplicates\tduplicate ratio");
+    }
+    println!("{}", header);
+    for (line, counts) in lines.iter().zip(&counts) {
+        let bounds: Vec<(Option<Growth>, bool)> = line.rules.iter().map(|r| bounds(r)).collect();
+        let growth = match bounds.iter().map(|(g, _)| *g).collect::<Option<Vec<Growth>>>() {
+            _ if line.incomplete => "unsupported functions".to_string(),
+            Some(growths) => growths.into_iter().max_by_key(|g| g.value()).map_or("-".to_string(), |g| g.to_string()),
+            None => "unknown".to_string()
+        };
+        let rejects = if bounds.iter().any(|(_, r)| *r) { "yes" } else { "no" };
+        let mut row = format!("{}:{}\t{}\t{}\t{}", line.path, line.number, line.text, growth, rejects);
+        if words.is_some() {
+            let ratio = if counts.candidates == 0 { 0.0 } else { counts.duplicates as f64 / counts.candidates as f64 };
+            row.push_str(&format!("\t{}\t{}\t{}\t{:.3}", counts.candidates, counts.rejected, counts.duplicates, ratio));
+        }
+        println!("{}", row);
+    }
+
+    eprintln!("{} lines, {} rule lines.", lines.len(), rule_lines);
+    let words = match &words {
+        Some(words) => words,
+        None => return
+    };
+    let candidates: u64 = counts.iter().map(|c| c.candidates - c.duplicates).sum();
+    let amplification = if sample.is_empty() { 0.0 } else { candidates as f64 / sample.len() as f64 };
+    let scale = if sample.is_empty() { 0.0 } else { words.len() as f64 / sample.len() as f64 };
+    let duplicates = if candidates == 0 { 0.0 } else { 1.0 - unique / candidates as f64 };
+    eprintln!(
+        "Sample of {} of {} words: {} candidates, amplification {:.2}, about {:.0} distinct ({:.1}% duplicates across words).",
+        sample.len(), words.len(), candidates, amplification, unique, duplicates * 100.0
+    );
+    eprintln!(
+        "Keyspace {} words x rule lines, estimated {:.0} candidates and about {:.0} distinct for the whole wordlist.",
+        words.len() as u64 * rule_lines as u64, candidates as f64 * scale, unique * scale
+    );
+}
+
+/// Counts of every line over `words`, and the estimated number of distinct
+/// candidates. Like a run, candidates an earlier rule line produced for the
+/// same word are duplicates.
+fn apply(lines: &[Line], words: &[&String]) -> (Vec<Counts>, f64) {
+    let mut counts: Vec<Counts> = lines.iter().map(|_| Counts::default()).collect();
+    let mut distinct = HyperLogLog::default();
+    for word in words {
+        let mut seen = HashSet::new();
+        for (line, counts) in lines.iter().zip(counts.iter_mut()) {
+            for rules in &line.rules {
+                match rule::apply_line(rules, word) {
+                    Some(candidate) => {
+                        counts.candidates += 1;
+                        distinct.add(&candidate);
+                        if !seen.insert(candidate) {
+                            counts.duplicates += 1;
+                        }
+                    },
+                    None => counts.rejected += 1
+                }
+            }
+        }
+    }
+    (counts, distinct.estimate())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn growth_saturates() {
+        let line = &source::read_lines("test.rule", &"p9 ".repeat(19), None)[0];
+        let (growth, rejects) = bounds(&line.rules[0]);
+        let growth = growth.unwrap();
+        assert_eq!(growth.value(), i64::MAX);
+        assert_eq!(growth.to_string(), "unbounded");
+        assert!(!rejects);
+
+        let line = &source::read_lines("test.rule", "d $1 '5", None)[0];
+        assert_eq!(bounds(&line.rules[0]).0.unwrap().to_string(), "min(2n+1, 5)");
+    }
+}
@@ -0,0 +1,60 @@
+use crate::wordlist::Generator;
+
+/// Every word of the left list followed by every word of the right list,
+/// joined by a separator. The right list changes fastest.
+pub struct Combinator {
+    left: Vec<String>,
+    right: Vec<String>,
+    separator: String,
+}
+
+impl Combinator {
+    pub fn new(left: Vec<String>, right: Vec<String>, separator: String) -> Combinator {
+        Combinator { left, right, separator }
+    }
+}
+
+impl Generator for Combinator {
+    fn keyspace(&self) -> u64 {
+        self.left.len() as u64 * self.right.len() as u64
+    }
+
+    fn word(&self, index: u64) -> Option<String> {
+        if index >= self.keyspace() {
+            return None;
+        }
+        let right = self.right.len() as u64;
+        let (l, r) = ((index / right) as usize, (index % right) as usize);
+
+        let mut out = String::with_capacity(self.left[l].len() + self.separator.len() + self.right[r].len());
+        out.push_str(&self.left[l]);
+        out.push_str(&self.separator);
+        out.push_str(&self.right[r]);
+        Some(out)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn words(words: &[&str]) -> Vec<String> {
+        words.iter().map(|w| w.to_string()).collect()
+    }
+
+    #[test]
+    fn left_then_right() {
+        let combinator = Combinator::new(words(&["a", "b"]), words(&["1", "2", "3"]), "-".to_string());
+        assert_eq!(combinator.keyspace(), 6);
+        let all: Vec<String> = (0..6).map(|i| combinator.word(i).unwrap()).collect();
+        assert_eq!(all, ["a-1", "a-2", "a-3", "b-1", "b-2", "b-3"]);
+        assert_eq!(combinator.word(6), None);
+    }
+
+    #[test]
+    fn empty_side() {
+        let combinator = Combinator::new(words(&["a"]), Vec::new(), String::new());
+        assert_eq!(combinator.keyspace(), 0);
+        assert_eq!(combinator.word(0), None);
+    }
+}
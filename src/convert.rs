@@ -0,0 +1,166 @@
+// `convert` subcommand, rewrites a rule file in the other dialect
+
+use std::fs;
+
+use crate::jtr;
+use crate::printer;
+use crate::rule::{Dialect, Rule};
+use crate::source::{self, Options, Shared};
+
+/// Options shared with the other subcommands
+const SHARED: &[Shared] = &[];
+
+fn print_usage(program: &str) {
+    eprintln!("Usage: {} convert [options] <rule file>", program);
+    eprintln!("Converts a rule file between the hashcat and the John the Ripper dialect.");
+    eprintln!("Lines that cannot be represented are written as comments.");
+    eprintln!("Options:");
+    eprintln!("  --from <hashcat|jtr>          Dialect of the rule file (default: detected).");
+    eprintln!("  --to <hashcat|jtr>            Dialect to write (default: the other one).");
+    eprintln!("  --section <name>              JtR section to read, or to write (default: Converted).");
+    eprintln!("  -d                            Enable debug output.");
+    source::print_common_usage(SHARED);
+}
+
+fn parse_dialect(s: &str) -> Dialect {
+    match s.to_lowercase().as_str() {
+        "hashcat" => Dialect::Hashcat,
+        "jtr" | "john" => Dialect::Jtr,
+        _ => {
+            eprintln!("Unknown dialect {}, use hashcat or jtr.", s);
+            std::process::exit(1);
+        }
+    }
+}
+
+pub fn main(program: &str, args: &[String]) {
+    let mut debug = false;
+    let mut from: Option<Dialect> = None;
+    let mut to: Option<Dialect> = None;
+    let mut section: Option<String> = None;
+
+    let Options { paths, .. } = source::parse_args(program, args, print_usage, SHARED, |arg, args| {
+        match arg {
+            "--from" => from = Some(parse_dialect(&args.value("dialect", "--from <hashcat|jtr>"))),
+            "--to" => to = Some(parse_dialect(&args.value("dialect", "--to <hashcat|jtr>"))),
+            "--section" => section = Some(args.value("section name", "--section <name>")),
+            "-d" => debug = true,
+            _ => return false
+        }
+        true
+    });
+
+    let path = match paths.as_slice() {
+        [path] => path,
+        _ => {
+            print_usage(program);
+            std::process::exit(1);
+        }
+    };
+    let content = match fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(e) => {
+            eprintln!("Could not read file {}: {}", path, e);
+            std::process::exit(1);
+        }
+    };
+
+    let from = from.unwrap_or(if jtr::is_jtr(&content) { Dialect::Jtr } else { Dialect::Hashcat });
+    let to = to.unwrap_or(if from == Dialect::Jtr { Dialect::Hashcat } else { Dialect::Jtr });
+
+    let mut converted = 0;
+    let mut failed = 0;
+    if to == Dialect::Jtr {
+        println!("[List.Rules:{}]", section.as_deref().unwrap_or("Converted"));
+    }
+
+    for (line, compiled) in compile(&content, from, section.as_deref(), debug) {
+        let compiled = match compiled {
+            // Blank lines and comments
+            None => {
+                match line.trim_start().strip_prefix(';') {
+                    Some(comment) if to == Dialect::Hashcat => println!("#{}", comment),
+                    _ => println!("{}", line)
+                }
+                continue;
+            },
+            Some(compiled) => compiled
+        };
+
+        let printed = compiled.and_then(|lines| {
+            lines.iter().map(|l| printer::print_line(l, to)).collect::<Result<Vec<String>, String>>()
+        });
+        match printed {
+            Ok(lines) => {
+                converted += 1;
+                lines.iter().for_each(|l| println!("{}", l));
+            },
+            Err(e) => {
+                failed += 1;
+                println!("# Not converted ({}): {}", e, line);
+            }
+        }
+    }
+
+    eprintln!("Converted {} lines, {} could not be represented.", converted, failed);
+}
+
+/// The rule lines of a line in a rule file, a JtR line can expand into many
+type Compiled = Result<Vec<Vec<Rule>>, String>;
+
+/// The lines of a rule file, with their rule lines or `None` for comments
+fn compile<'a>(content: &'a str, dialect: Dialect, section: Option<&str>, debug: bool) -> Vec<(&'a str, Option<Compiled>)> {
+    match dialect {
+        Dialect::Jtr => jtr::section_lines(content, section).into_iter().map(|(_, line)| {
+            if jtr::is_comment(line) {
+                (line, None)
+            } else {
+                (line, Some(jtr::compile_line(line)))
+            }
+        }).collect(),
+        Dialect::Hashcat => content.lines().map(|line| {
+            if line.trim().is_empty() || line.starts_with('#') {
+                return (line, None);
+            }
+            let (rules, skipped) = crate::parse_rules_checked(line, debug).swap_remove(0);
+            if skipped.is_empty() {
+                (line, Some(Ok(vec![rules])))
+            } else {
+                let skipped: String = skipped.iter().collect();
+                (line, Some(Err(format!("unsupported functions {}", skipped))))
+            }
+        }).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rule;
+
+    const JTR: &str = "[List.Rules:Test]\n:\nl\nu\nc\nC\nt\nT2\nr\nd\nf\n{\n}\n$1\n^a\n\\[\n]\nD3\nx14\nO12\ni3!\no0X\nsa@\n@a\n'5\nc $1 $2\nA0\"12\"\nAz\"ab\"\nsa[4@]\n<5\n>3\n!x\n/a\n";
+    const WORDS: [&str; 5] = ["password", "Admin", "a", "qwertyuiop", "Straße"];
+
+    #[test]
+    fn jtr_to_hashcat_and_back() {
+        let lines = compile(JTR, Dialect::Jtr, None, false);
+        assert_eq!(lines.len(), JTR.lines().count() - 1);
+        for (line, compiled) in lines {
+            let jtr = compiled.unwrap().unwrap_or_else(|e| panic!("{}: {}", line, e));
+            for jtr_line in jtr {
+                let hashcat = printer::print_line(&jtr_line, Dialect::Hashcat).unwrap_or_else(|e| panic!("{}: {}", line, e));
+                let from_hashcat = crate::parse_rules(&hashcat, false).swap_remove(0);
+                let back = printer::print_line(&from_hashcat, Dialect::Jtr).unwrap();
+                let mut from_back = jtr::compile_line(&back).unwrap();
+                assert_eq!(from_back.len(), 1, "{} -> {} -> {}", line, hashcat, back);
+                let from_back = from_back.swap_remove(0);
+
+                for word in WORDS {
+                    let expected = rule::apply_line(&jtr_line, word);
+                    assert_eq!(rule::apply_line(&from_hashcat, word), expected, "{} -> {} on {}", line, hashcat, word);
+                    assert_eq!(rule::apply_line(&from_back, word), expected, "{} -> {} -> {} on {}", line, hashcat, back, word);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,165 @@
+// `cover` subcommand, picks a small subset of a rule file that still
+// reproduces most of the cracks
+//
+// Greedy set cover: the rule line adding the most cracks not yet covered is
+// picked until the target is reached. Gains only shrink as more cracks are
+// covered, so a stale gain is an upper bound and only the line on top of the
+// queue has to be recomputed.
+
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap, HashSet};
+
+use crate::rank;
+use crate::source::{self, Options, Shared};
+
+/// Options shared with the other subcommands
+const SHARED: &[Shared] = &[Shared::Wordlist, Shared::Section];
+
+fn print_usage(program: &str) {
+    eprintln!("Usage: {} cover [options] <rule file>...", program);
+    eprintln!("Prints the smallest set of rule lines found that reproduces the target share of the");
+    eprintln!("cracks of all rule lines, best line first.");
+    eprintln!("Options:");
+    eprintln!("  -w <path to wordlist>         Base wordlist the rules are applied to.");
+    eprintln!("  --cracked <path>              Cracked passwords, one per line.");
+    eprintln!("  --debug-file <path>           hashcat debug file of --debug-mode=4 with word:rule:plain lines,");
+    eprintln!("                                or rule:word:plain lines, instead of -w and --cracked.");
+    eprintln!("  --target <percent>            Share of the cracks to reproduce (default 100).");
+    eprintln!("  --curve <path>                Write the coverage curve to <path> instead of stderr.");
+    source::print_common_usage(SHARED);
+}
+
+pub fn main(program: &str, args: &[String]) {
+    let mut cracked_path: Option<String> = None;
+    let mut debug_path: Option<String> = None;
+    let mut curve_path: Option<String> = None;
+    let mut target: f64 = 100.0;
+
+    let Options { paths, mut wordlists, section, .. } =
+        source::parse_args(program, args, print_usage, SHARED, |arg, args| {
+            match arg {
+                "--cracked" => cracked_path = Some(args.value("cracked passwords location", "--cracked <path>")),
+                "--debug-file" => debug_path = Some(args.value("debug file location", "--debug-file <path>")),
+                "--target" => {
+                    match args.next().and_then(|t| t.trim_end_matches('%').parse::<f64>().ok()) {
+                        Some(t) if t > 0.0 && t <= 100.0 => target = t,
+                        _ => {
+                            eprintln!("Invalid target. Usage: --target <percent between 0 and 100>.");
+                            std::process::exit(1);
+                        }
+                    }
+                },
+                "--curve" => curve_path = Some(args.value("curve location", "--curve <path>")),
+                _ => return false
+            }
+            true
+        });
+    let wordlist_path = wordlists.pop();
+
+    if paths.is_empty() {
+        print_usage(program);
+        std::process::exit(1);
+    }
+
+    let lines = source::read_files(&paths, section.as_deref());
+    let header = source::header(&lines);
+    let plains = match (debug_path, wordlist_path, cracked_path) {
+        (Some(debug_path), _, _) => rank::read_debug_file(&lines, &debug_path),
+        (None, Some(wordlist_path), Some(cracked_path)) => {
+            let words = crate::read_words(Some(&wordlist_path));
+            let cracked: HashSet<String> = crate::read_words(Some(&cracked_path)).into_iter().collect();
+            rank::apply(&lines, &words, &cracked)
+        },
+        _ => {
+            eprintln!("Either --debug-file or -w and --cracked are required.");
+            std::process::exit(1);
+        }
+    };
+
+    // Cracks as numbers, lines with functions the engine skips have none
+    let mut ids: HashMap<&str, usize> = HashMap::new();
+    let sets: Vec<Vec<usize>> = plains.iter().map(|found| found.iter().map(|p| {
+        let next = ids.len();
+        *ids.entry(p.as_str()).or_insert(next)
+    }).collect()).collect();
+    let total = ids.len();
+    let goal = (total as f64 * target / 100.0).ceil() as usize;
+
+    let picked = greedy(&sets, goal);
+    if let Some(header) = &header {
+        println!("{}", header);
+    }
+
+    let mut curve = vec!["rules\tcovered\tpercent".to_string()];
+    for (n, (i, covered)) in picked.iter().enumerate() {
+        println!("{}", lines[*i].text);
+        let percent = if total == 0 { 100.0 } else { *covered as f64 * 100.0 / total as f64 };
+        curve.push(format!("{}\t{}\t{:.2}", n + 1, covered, percent));
+    }
+
+    let written = match &curve_path {
+        Some(path) => std::fs::write(path, curve.join("\n") + "\n"),
+        None => {
+            curve.iter().for_each(|c| eprintln!("{}", c));
+            Ok(())
+        }
+    };
+    if let Err(e) = written {
+        eprintln!("Could not write curve {}: {}", curve_path.unwrap_or_default(), e);
+        std::process::exit(1);
+    }
+
+    let incomplete = lines.iter().filter(|l| l.incomplete).count();
+    eprintln!(
+        "Picked {} of {} rule lines, covering {} of the {} cracks of all rule lines ({} lines with unsupported functions left out).",
+        picked.len(), lines.len(), picked.last().map_or(0, |p| p.1), total, incomplete
+    );
+}
+
+/// Picks lines until `goal` cracks are covered. Returns the picked lines in
+/// order, with the number of cracks covered after each of them.
+fn greedy(sets: &[Vec<usize>], goal: usize) -> Vec<(usize, usize)> {
+    let mut covered: HashSet<usize> = HashSet::new();
+    let mut picked = Vec::new();
+    // Possibly stale gains, the earlier line wins ties
+    let mut queue: BinaryHeap<(usize, Reverse<usize>)> = sets.iter().enumerate()
+        .filter(|(_, set)| !set.is_empty())
+        .map(|(i, set)| (set.len(), Reverse(i)))
+        .collect();
+
+    while covered.len() < goal {
+        let (gain, Reverse(i)) = match queue.pop() {
+            Some(top) => top,
+            None => break
+        };
+        let current = sets[i].iter().filter(|p| !covered.contains(p)).count();
+        if current == 0 {
+            continue;
+        }
+        if current < gain {
+            queue.push((current, Reverse(i)));
+            continue;
+        }
+        covered.extend(&sets[i]);
+        picked.push((i, covered.len()));
+    }
+    picked
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn greedy_order_and_cutoff() {
+        // Cracks 0-5, line 2 covers most, then lines 0 and 3 tie at 1 new
+        // crack and the earlier one wins, line 1 adds nothing anymore
+        let sets = vec![vec![0, 1], vec![2], vec![1, 2, 3, 4], vec![5], vec![]];
+        assert_eq!(greedy(&sets, 6), vec![(2, 4), (0, 5), (3, 6)]);
+        assert_eq!(greedy(&sets, 5), vec![(2, 4), (0, 5)]);
+        assert_eq!(greedy(&sets, 3), vec![(2, 4)]);
+        assert_eq!(greedy(&sets, 0), vec![]);
+        // An unreachable goal stops when no line adds anything
+        assert_eq!(greedy(&sets, 7), vec![(2, 4), (0, 5), (3, 6)]);
+    }
+}
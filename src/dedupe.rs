@@ -0,0 +1,105 @@
+// `dedupe-rules` subcommand, drops rule lines that behave like an earlier one
+//
+// Every rule line is applied to a set of probe words. Lines producing the
+// same candidates, or rejecting the same words, on every probe are taken to be
+// the same transform and only the first of them is kept.
+
+use std::collections::HashMap;
+use std::fs;
+use std::io::Write;
+
+use crate::rule;
+use crate::source::{self, Line, Options, Shared};
+
+/// Probe words covering different lengths, cases, digits and symbols
+pub const PROBES: &[&str] = &[
+    "", "a", "A", "1", "ab", "aB", "Ab", "12", "a1", "!a",
+    "abc", "ABC", "123", "abc123", "123abc", "Password", "password", "PASSWORD",
+    "password1", "Password1!", "p@ssw0rd", "P4$$W0RD", "hello world", "letmein",
+    "iloveyou", "qwerty", "summer2024", "Summer2024!", "aaaa", "abab", "aBcDeFgH",
+    "racecar", "monkey", "dragon", "sunshine", "football", "princess", "welcome",
+    "zZ9_-+=", "a.b,c;d:e", "~`!@#$%^&*()", "abcdefghijklmnopqrstuvwxyz",
+    "ABCDEFGHIJKLMNOPQRSTUVWXYZ0123456789abcdefghij", "0123456789",
+    "trustno1", "baseball", "shadow", "master", "michael", "jordan23",
+    // Case mapping changes the length or differs from ASCII
+    "straße", "STRASSE", "İstanbul", "µ", "ǅ", "ﬁx", "Ελλάς",
+];
+
+/// Options shared with the other subcommands
+const SHARED: &[Shared] = &[Shared::Wordlist, Shared::Report, Shared::Section];
+
+fn print_usage(program: &str) {
+    eprintln!("Usage: {} dedupe-rules [options] <rule file>...", program);
+    eprintln!("Prints the rule lines of the rule files without the ones that behave like");
+    eprintln!("an earlier line on every probe word.");
+    eprintln!("Options:");
+    eprintln!("  -w <path to wordlist>         Add the words of a wordlist to the built-in probe words.");
+    eprintln!("  --report <path>               Write the dropped lines to <path> instead of stderr.");
+    source::print_common_usage(SHARED);
+}
+
+/// What every rule line of `line` makes of every probe word, None where it rejects it
+pub fn fingerprint(line: &Line, probes: &[String]) -> Vec<Option<String>> {
+    line.rules.iter()
+        .flat_map(|rules| probes.iter().map(move |p| rule::apply_line(rules, p)))
+        .collect()
+}
+
+pub fn main(program: &str, args: &[String]) {
+    let Options { paths, wordlists: probe_paths, report: report_path, section } =
+        source::parse_args(program, args, print_usage, SHARED, |_, _| false);
+
+    if paths.is_empty() {
+        print_usage(program);
+        std::process::exit(1);
+    }
+
+    let mut probes: Vec<String> = PROBES.iter().map(|p| p.to_string()).collect();
+    for path in &probe_paths {
+        probes.extend(crate::read_words(Some(path)));
+    }
+
+    let lines = source::read_files(&paths, section.as_deref());
+    let header = source::header(&lines);
+
+    if let Some(header) = &header {
+        println!("{}", header);
+    }
+    // Index of the first line of every behaviour
+    let mut seen: HashMap<Vec<Option<String>>, usize> = HashMap::new();
+    let mut report = Vec::new();
+    let mut kept = 0;
+    for (i, line) in lines.iter().enumerate() {
+        if !line.incomplete {
+            let fingerprint = fingerprint(line, &probes);
+            if let Some(first) = seen.get(&fingerprint) {
+                let first: &Line = &lines[*first];
+                report.push(format!("{}:{}: {}    same as {}:{}: {}", line.path, line.number, line.text, first.path, first.number, first.text));
+                continue;
+            }
+            seen.insert(fingerprint, i);
+        }
+        println!("{}", line.text);
+        kept += 1;
+    }
+
+    let incomplete = lines.iter().filter(|l| l.incomplete).count();
+    let summary = format!(
+        "Kept {} of {} rule lines, dropped {} ({} lines with unsupported functions kept unchecked).",
+        kept, lines.len(), report.len(), incomplete
+    );
+    match report_path {
+        Some(path) => {
+            let written = fs::File::create(&path).and_then(|mut file| {
+                report.iter().try_for_each(|r| writeln!(file, "{}", r))?;
+                writeln!(file, "{}", summary)
+            });
+            if let Err(e) = written {
+                eprintln!("Could not write report {}: {}", path, e);
+                std::process::exit(1);
+            }
+        },
+        None => report.iter().for_each(|r| eprintln!("{}", r))
+    }
+    eprintln!("{}", summary);
+}
@@ -0,0 +1,149 @@
+// `diff` subcommand, compares what two rule files do rather than their text
+//
+// Both files are applied to the probe words of `dedupe-rules`. A line is
+// unique to a side if no line of the other side behaves the same on every
+// probe; lines behaving the same with different text are equivalent. Lines
+// with unsupported functions can only be compared by their text.
+
+use std::collections::{HashMap, HashSet};
+
+use crate::dedupe;
+use crate::source::{self, Line, Options, Shared};
+
+/// Options shared with the other subcommands
+const SHARED: &[Shared] = &[Shared::Wordlist, Shared::Section];
+
+fn print_usage(program: &str) {
+    eprintln!("Usage: {} diff [options] <old rule file> <new rule file>", program);
+    eprintln!("Prints the rule lines only one of the files has (- old, + new) and the lines that behave");
+    eprintln!("the same despite a different text (=), judged by their candidates for the probe words.");
+    eprintln!("Options:");
+    eprintln!("  -w <path to wordlist>         Add the words of a wordlist to the built-in probe words.");
+    source::print_common_usage(SHARED);
+}
+
+pub fn main(program: &str, args: &[String]) {
+    let Options { paths, wordlists: probe_paths, section, .. } =
+        source::parse_args(program, args, print_usage, SHARED, |_, _| false);
+
+    if paths.len() != 2 {
+        print_usage(program);
+        std::process::exit(1);
+    }
+
+    let mut probes: Vec<String> = dedupe::PROBES.iter().map(|p| p.to_string()).collect();
+    for path in &probe_paths {
+        probes.extend(crate::read_words(Some(path)));
+    }
+
+    let old = source::read_files(&paths[..1], section.as_deref());
+    let new = source::read_files(&paths[1..], section.as_deref());
+    let Comparison { removed, added, equivalent } = compare(&old, &new, &probes);
+    for line in &removed {
+        println!("- {}", describe(line));
+    }
+    for line in &added {
+        println!("+ {}", describe(line));
+    }
+    for (old_line, new_line) in &equivalent {
+        println!("= {}    {}", describe(old_line), describe(new_line));
+    }
+
+    let old_candidates = candidates(&old, &probes);
+    let new_candidates = candidates(&new, &probes);
+    let common = old_candidates.intersection(&new_candidates).count();
+    let all = old_candidates.union(&new_candidates).count();
+    let percent = |n: usize, of: usize| if of == 0 { 100.0 } else { n as f64 * 100.0 / of as f64 };
+    eprintln!(
+        "{} lines only in {}, {} only in {}, {} equivalent with a different text.",
+        removed.len(), paths[0], added.len(), paths[1], equivalent.len()
+    );
+    eprintln!(
+        "Candidates for {} probe words: {} and {}, {:.1}% overlap. {} keeps {:.1}% of the candidates of {}.",
+        probes.len(), old_candidates.len(), new_candidates.len(), percent(common, all),
+        paths[1], percent(common, old_candidates.len()), paths[0]
+    );
+}
+
+/// The lines only one side has, and the lines of the new side behaving like
+/// an old line with a different text
+struct Comparison<'a> {
+    removed: Vec<&'a Line>,
+    added: Vec<&'a Line>,
+    /// The first old line of the behaviour and the new line
+    equivalent: Vec<(&'a Line, &'a Line)>,
+}
+
+fn compare<'a>(old: &'a [Line], new: &'a [Line], probes: &[String]) -> Comparison<'a> {
+    let old_keys: Vec<Key> = old.iter().map(|l| key(l, probes)).collect();
+    let new_keys: Vec<Key> = new.iter().map(|l| key(l, probes)).collect();
+
+    // First line of every behaviour on each side
+    let mut old_first: HashMap<&Key, usize> = HashMap::new();
+    for (i, k) in old_keys.iter().enumerate() {
+        old_first.entry(k).or_insert(i);
+    }
+    let new_known: HashSet<&Key> = new_keys.iter().collect();
+
+    let removed = old.iter().zip(&old_keys).filter(|(_, k)| !new_known.contains(k)).map(|(l, _)| l).collect();
+    let added = new.iter().zip(&new_keys).filter(|(_, k)| !old_first.contains_key(k)).map(|(l, _)| l).collect();
+
+    // Equivalent lines, unless the other side also has the same text
+    let old_texts: HashSet<&str> = old.iter().map(|l| l.text.as_str()).collect();
+    let new_texts: HashSet<&str> = new.iter().map(|l| l.text.as_str()).collect();
+    let mut equivalent = Vec::new();
+    for (line, k) in new.iter().zip(&new_keys) {
+        if let Some(&i) = old_first.get(k) {
+            if !old_texts.contains(line.text.as_str()) && !new_texts.contains(old[i].text.as_str()) {
+                equivalent.push((&old[i], line));
+            }
+        }
+    }
+    Comparison { removed, added, equivalent }
+}
+
+/// What a line is compared by, its behaviour or, with unsupported functions, its text
+#[derive(PartialEq, Eq, Hash)]
+enum Key {
+    Behaviour(Vec<Option<String>>),
+    Text(String),
+}
+
+fn key(line: &Line, probes: &[String]) -> Key {
+    match line.incomplete {
+        true => Key::Text(line.text.clone()),
+        false => Key::Behaviour(dedupe::fingerprint(line, probes))
+    }
+}
+
+fn describe(line: &Line) -> String {
+    let unsupported = if line.incomplete { " (unsupported functions, compared by text)" } else { "" };
+    format!("{}:{}: {}{}", line.path, line.number, line.text, unsupported)
+}
+
+/// All candidates of `lines` for the probe words
+fn candidates(lines: &[Line], probes: &[String]) -> HashSet<String> {
+    lines.iter().filter(|l| !l.incomplete).flat_map(|l| probes.iter().flat_map(move |p| l.apply(p))).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn texts(lines: &[&Line]) -> Vec<String> {
+        lines.iter().map(|l| format!("{}:{}", l.number, l.text)).collect()
+    }
+
+    #[test]
+    fn added_removed_and_equivalent() {
+        let probes: Vec<String> = dedupe::PROBES.iter().map(|p| p.to_string()).collect();
+        let old = source::read_lines("old.rule", ":\n$1 c\nr\nu\nk\n", None);
+        let new = source::read_lines("new.rule", ":\nc $1\nd\nu\nk\nK\n", None);
+        let Comparison { removed, added, equivalent } = compare(&old, &new, &probes);
+        assert_eq!(texts(&removed), ["3:r"]);
+        // `k` is not implemented, only its text is compared
+        assert_eq!(texts(&added), ["3:d", "6:K"]);
+        let equivalent: Vec<(String, String)> = equivalent.iter().map(|(o, n)| (o.text.clone(), n.text.clone())).collect();
+        assert_eq!(equivalent, [("$1 c".to_string(), "c $1".to_string())]);
+    }
+}
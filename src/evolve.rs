@@ -0,0 +1,267 @@
+// `evolve` subcommand, breeds rule lines cracking passwords other rules miss
+//
+// A genetic algorithm over rule lines. The fitness of a line is the number of
+// cracked passwords it produces from the base wordlist that neither the plain
+// words nor the given rule files produce. Every generation keeps its best
+// lines and fills up with children of lines picked by tournament: a prefix of
+// one parent joined to a suffix of another, then a function added, removed or
+// replaced.
+
+use std::collections::{HashMap, HashSet};
+
+use crate::generate::{self, GenerateOptions};
+use crate::printer;
+use crate::random::Rng;
+use crate::rank;
+use crate::rule::{self, Dialect, Rule};
+use crate::source::{self, Options, Shared};
+
+/// Lines competing for every parent
+const TOURNAMENT: usize = 3;
+
+/// Options shared with the other subcommands
+const SHARED: &[Shared] = &[Shared::Wordlist, Shared::Section];
+
+fn print_usage(program: &str) {
+    eprintln!("Usage: {} evolve [options] [rule file]...", program);
+    eprintln!("Prints the best rule lines found that crack passwords the plain words and the rule files don't.");
+    eprintln!("Options:");
+    eprintln!("  -w <path to wordlist>         Base wordlist the rules are applied to.");
+    eprintln!("  --cracked <path>              Cracked passwords, one per line.");
+    eprintln!("  --generations <n>             Number of generations (default 20).");
+    eprintln!("  --population <n>              Rule lines per generation (default 200).");
+    eprintln!("  --keep <n>                    Maximum number of rule lines to print (default 50).");
+    eprintln!("  --func-max <n>                Maximum number of functions per rule line (default 4).");
+    eprintln!("  --weights <path>              Function weights, one <function> <weight> per line (default 1).");
+    eprintln!("  --seed <n>                    Seed of the random choices (default 0).");
+    source::print_common_usage(SHARED);
+}
+
+pub fn main(program: &str, args: &[String]) {
+    let mut cracked_path: Option<String> = None;
+    let mut generations: usize = 20;
+    let mut population_size: usize = 200;
+    let mut keep: usize = 50;
+    let mut options = GenerateOptions::default();
+    let mut seed: u64 = 0;
+
+    let Options { paths, mut wordlists, section, .. } =
+        source::parse_args(program, args, print_usage, SHARED, |arg, args| {
+            match arg {
+                "--cracked" => cracked_path = Some(args.value("cracked passwords location", "--cracked <path>")),
+                "--generations" => generations = args.number(arg) as usize,
+                "--population" => population_size = args.number(arg) as usize,
+                "--keep" => keep = args.number(arg) as usize,
+                "--func-max" => options.func_max = args.number(arg) as usize,
+                "--seed" => seed = args.number(arg),
+                "--weights" => {
+                    let path = args.value("weights location", "--weights <path>");
+                    if let Err(e) = options.read_weights(&path) {
+                        eprintln!("Could not read weights {}: {}", path, e);
+                        std::process::exit(1);
+                    }
+                },
+                _ => return false
+            }
+            true
+        });
+    let wordlist_path = wordlists.pop();
+
+    let (wordlist_path, cracked_path) = match (wordlist_path, cracked_path) {
+        (Some(wordlist_path), Some(cracked_path)) => (wordlist_path, cracked_path),
+        _ => {
+            eprintln!("-w and --cracked are required.");
+            print_usage(program);
+            std::process::exit(1);
+        }
+    };
+    if population_size < 2 {
+        eprintln!("The population needs at least 2 rule lines.");
+        std::process::exit(1);
+    }
+    if let Err(e) = options.check() {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    }
+
+    let words = crate::read_words(Some(&wordlist_path));
+    let mut cracked: HashSet<String> = crate::read_words(Some(&cracked_path)).into_iter().collect();
+
+    // Only passwords nothing else cracks count
+    let lines = source::read_files(&paths, section.as_deref());
+    let known: HashSet<String> = rank::apply(&lines, &words, &cracked).into_iter().flatten().collect();
+    let plain: HashSet<&String> = words.iter().collect();
+    let total = cracked.len();
+    cracked.retain(|c| !known.contains(c) && !plain.contains(c));
+    eprintln!("{} of {} cracked passwords are not produced by the words or rule files.", cracked.len(), total);
+
+    let mut rng = Rng::new(seed);
+    let mut evolution = Evolution { words: &words, cracked: &cracked, fitness: HashMap::new() };
+    let population = generate::generate(population_size, &options, &mut rng);
+    if population.is_empty() {
+        eprintln!("No rule lines could be generated with these weights.");
+        std::process::exit(1);
+    }
+    evolution.run(population, population_size, generations, &options, &mut rng);
+
+    let mut best: Vec<(&String, &(usize, Vec<Rule>))> = evolution.fitness.iter().filter(|(_, (f, _))| *f > 0).collect();
+    best.sort_by_key(|(text, (fitness, _))| (std::cmp::Reverse(*fitness), text.len(), *text));
+
+    // Lines only cracking passwords of better lines add nothing
+    let mut found: HashSet<String> = HashSet::new();
+    let mut printed = 0;
+    for (text, (_, line)) in best {
+        if printed == keep {
+            break;
+        }
+        let hits = evolution.hits(line);
+        if !hits.is_subset(&found) {
+            println!("{}", text);
+            found.extend(hits);
+            printed += 1;
+        }
+    }
+    eprintln!("Printed {} rule lines, cracking {} of the {} passwords left.", printed, found.len(), cracked.len());
+}
+
+/// Words, the passwords left to crack and the fitness of every line evaluated so far
+struct Evolution<'a> {
+    words: &'a [String],
+    cracked: &'a HashSet<String>,
+    /// Fitness and the line, by printed line
+    fitness: HashMap<String, (usize, Vec<Rule>)>,
+}
+
+impl Evolution<'_> {
+    /// Scores `population` and breeds `generations` generations of `size`
+    /// lines from it, the fitness of every line ends up in `self.fitness`
+    fn run(&mut self, mut population: Vec<Vec<Rule>>, size: usize, generations: usize, options: &GenerateOptions, rng: &mut Rng) {
+        for generation in 0..=generations {
+            let mut scored: Vec<(usize, Vec<Rule>)> = population.into_iter().map(|line| (self.fitness(&line), line)).collect();
+            // Stable, the order of equally fit lines only depends on the seed
+            scored.sort_by_key(|(fitness, _)| std::cmp::Reverse(*fitness));
+            eprintln!(
+                "Generation {}: best {} new cracks, {} rule lines with new cracks found.",
+                generation, scored[0].0, self.fitness.values().filter(|(f, _)| *f > 0).count()
+            );
+            if generation == generations {
+                break;
+            }
+            population = breed(&scored, size, options, rng);
+        }
+    }
+
+    /// Number of passwords left that `line` cracks, 0 for lines that can't be printed
+    fn fitness(&mut self, line: &[Rule]) -> usize {
+        let text = match printer::print_line(line, Dialect::Hashcat) {
+            Ok(text) => text,
+            Err(_) => return 0
+        };
+        if let Some((fitness, _)) = self.fitness.get(&text) {
+            return *fitness;
+        }
+        let fitness = self.hits(line).len();
+        self.fitness.insert(text, (fitness, line.to_vec()));
+        fitness
+    }
+
+    /// The passwords left that `line` cracks
+    fn hits(&self, line: &[Rule]) -> HashSet<String> {
+        self.words.iter()
+            .filter_map(|w| rule::apply_line(line, w))
+            .filter(|c| self.cracked.contains(c))
+            .collect()
+    }
+}
+
+/// The next generation: the best tenth of `scored`, which is sorted by
+/// fitness, and children of lines picked by tournament
+fn breed(scored: &[(usize, Vec<Rule>)], size: usize, options: &GenerateOptions, rng: &mut Rng) -> Vec<Vec<Rule>> {
+    let mut next: Vec<Vec<Rule>> = scored.iter().take((size / 10).max(1)).map(|(_, line)| line.clone()).collect();
+    let mut seen: HashSet<String> = HashSet::new();
+    next.retain(|line| seen.insert(format!("{:?}", line)));
+
+    // Children identical to a line already picked are tried again, a few times
+    let mut attempts = size.saturating_mul(10);
+    while next.len() < size && attempts > 0 {
+        attempts -= 1;
+        let mut child = tournament(scored, rng).clone();
+        if rng.below(2) == 0 {
+            child = crossover(&child, tournament(scored, rng), options.func_max, rng);
+        }
+        mutate(&mut child, options, rng);
+        if seen.insert(format!("{:?}", child)) {
+            next.push(child);
+        }
+    }
+    next
+}
+
+/// The fittest of `TOURNAMENT` randomly picked lines
+fn tournament<'a>(scored: &'a [(usize, Vec<Rule>)], rng: &mut Rng) -> &'a Vec<Rule> {
+    // Sorted by fitness, the lowest index wins
+    let winner = (0..TOURNAMENT).map(|_| rng.below(scored.len() as u64) as usize).min().unwrap();
+    &scored[winner].1
+}
+
+/// A prefix of `a` followed by a non-empty suffix of `b`
+fn crossover(a: &[Rule], b: &[Rule], func_max: usize, rng: &mut Rng) -> Vec<Rule> {
+    let i = rng.below(a.len() as u64 + 1) as usize;
+    let j = rng.below(b.len() as u64) as usize;
+    let mut child: Vec<Rule> = a[..i].iter().chain(&b[j..]).cloned().collect();
+    child.truncate(func_max);
+    child
+}
+
+/// Adds, removes or replaces a random function
+fn mutate(line: &mut Vec<Rule>, options: &GenerateOptions, rng: &mut Rng) {
+    let at = rng.below(line.len() as u64) as usize;
+    match rng.below(3) {
+        0 if line.len() < options.func_max => line.insert(rng.below(line.len() as u64 + 1) as usize, generate::random_function(options, rng)),
+        1 if line.len() > 1 => { line.remove(at); },
+        _ => line[at] = generate::random_function(options, rng)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn words(words: &[&str]) -> Vec<String> {
+        words.iter().map(|w| w.to_string()).collect()
+    }
+
+    #[test]
+    fn fitness_counts_new_cracks() {
+        let base = words(&["pass", "abc", "Abc"]);
+        let cracked: HashSet<String> = words(&["pass1", "abc1", "PASS"]).into_iter().collect();
+        let mut evolution = Evolution { words: &base, cracked: &cracked, fitness: HashMap::new() };
+        assert_eq!(evolution.fitness(&[Rule::Append('1')]), 2);
+        assert_eq!(evolution.fitness(&[Rule::Uppercase()]), 1);
+        assert_eq!(evolution.fitness(&[Rule::Append('2')]), 0);
+        // JtR only functions can't be printed as hashcat rules
+        assert_eq!(evolution.fitness(&[Rule::SetVariable(0, rule::Pos::Length, rule::Pos::Fixed(1))]), 0);
+        assert_eq!(evolution.fitness.len(), 3);
+        assert_eq!(evolution.hits(&[Rule::Lowercase(), Rule::Append('1')]), words(&["pass1", "abc1"]).into_iter().collect());
+    }
+
+    #[test]
+    fn seeded_runs_repeat() {
+        let base = words(&["pass", "abc", "monkey", "dragon"]);
+        let cracked: HashSet<String> = words(&["pass1", "Abc", "yeknom", "dragon12", "PASS!"]).into_iter().collect();
+        let options = GenerateOptions { func_max: 3, ..GenerateOptions::default() };
+        let evolve = |seed| {
+            let mut rng = Rng::new(seed);
+            let mut evolution = Evolution { words: &base, cracked: &cracked, fitness: HashMap::new() };
+            let population = generate::generate(30, &options, &mut rng);
+            evolution.run(population, 30, 5, &options, &mut rng);
+            let mut fitness: Vec<(String, usize)> = evolution.fitness.into_iter().map(|(text, (f, _))| (text, f)).collect();
+            fitness.sort();
+            fitness
+        };
+        let first = evolve(7);
+        assert_eq!(first, evolve(7));
+        assert_ne!(first, evolve(8));
+        assert!(first.iter().all(|(_, f)| *f <= cracked.len()));
+    }
+}
@@ -0,0 +1,235 @@
+// Plain English descriptions of rule lines, and the `explain` subcommand
+//
+// The descriptions follow what this engine does with every function, where
+// that differs from hashcat the engine wins.
+
+use crate::rule::{self, Class, Pos, Rule};
+use crate::source::{self, Line, Options, Shared};
+
+/// What a rule line does, e.g. "Uppercase the first character, then append '1'"
+pub fn describe_line(line: &[Rule]) -> String {
+    let parts: Vec<String> = line.iter().filter(|r| **r != Rule::Nothing).map(describe).collect();
+    let joined = match parts.is_empty() {
+        true => "do nothing".to_string(),
+        false => parts.join(", then ")
+    };
+    let mut chars = joined.chars();
+    chars.next().map_or(String::new(), |first| first.to_uppercase().chain(chars).collect())
+}
+
+/// What a line of a rule file does, the first of its rule lines for JtR
+/// lines expanding into several
+pub fn describe_source(line: &Line) -> String {
+    match line.rules.as_slice() {
+        _ if line.incomplete => "Unsupported functions".to_string(),
+        [] => "No rule lines".to_string(),
+        [rules] => describe_line(rules),
+        [rules, ..] => format!("{} (first of {} rule lines)", describe_line(rules), line.rules.len())
+    }
+}
+
+/// What a single function does, starting in lower case
+pub fn describe(rule: &Rule) -> String {
+    describe_with(rule, None)
+}
+
+/// `positions` replaces the positions of the function, in the order they are written
+fn describe_with(rule: &Rule, positions: Option<&[Pos]>) -> String {
+    let pos = |i: usize, n: usize| match positions.and_then(|p| p.get(i)) {
+        Some(p) => position(p),
+        None => position(&Pos::Fixed(n))
+    };
+    match rule {
+        Rule::Nothing => "do nothing".to_string(),
+        Rule::Lowercase() => "lowercase all letters".to_string(),
+        Rule::Uppercase() => "uppercase all letters".to_string(),
+        Rule::Capitalize() => "uppercase the first character".to_string(),
+        Rule::InvertCapitalize() => "lowercase the first character and uppercase the rest".to_string(),
+        Rule::ToggleCase() => "toggle the case of all letters".to_string(),
+        Rule::ToggleAt(n) => format!("toggle the case of the character at position {}", pos(0, *n)),
+        Rule::Reverse() => "reverse the word".to_string(),
+        Rule::Duplicate() => "duplicate the word".to_string(),
+        Rule::DuplicateN(n) => format!("repeat the word {} times", n),
+        Rule::Reflect() => "append the word reversed".to_string(),
+        Rule::RotateLeft() => "move the first character to the end".to_string(),
+        Rule::RotateRight() => "move the last character to the front".to_string(),
+        Rule::Append(c) => format!("append {}", quote(*c)),
+        Rule::Prepend(c) => format!("prepend {}", quote(*c)),
+        Rule::TruncateLeft() => "delete the first character".to_string(),
+        Rule::TruncateRight() => "delete the last character".to_string(),
+        Rule::DeleteAt(n) => format!("delete the character at position {}", pos(0, *n)),
+        Rule::ExtractRange(n, m) => format!("keep only the {} characters from position {}", pos(1, *m), pos(0, *n)),
+        Rule::OmitRange(n, m) => format!("delete {} characters from position {}", pos(1, *m), pos(0, *n)),
+        Rule::InsertAt(n, c) => format!("insert {} at position {}", quote(*c), pos(0, *n)),
+        Rule::OverwriteAt(n, c) => format!("overwrite the character at position {} with {}", pos(0, *n), quote(*c)),
+        Rule::TruncateAt(n) => format!("keep only the first {} characters", pos(0, *n)),
+        Rule::Replace(x, y) => format!("replace every {} with {}", quote(*x), quote(*y)),
+        Rule::Purge(c) => format!("delete every {}", quote(*c)),
+        Rule::DuplicateFirstN(n) => format!("prepend the first character {} times", n),
+        Rule::DuplicateLastN(n) => format!("append the last character {} times", n),
+        Rule::DuplicateAll() => "double every character".to_string(),
+        Rule::ExtractMemory(n, m, i) => format!(
+            "insert {} characters of the memorized word from position {} at position {}",
+            pos(1, *m), pos(0, *n), pos(2, *i)
+        ),
+        Rule::AppendMemory() => "append the memorized word".to_string(),
+        Rule::PrependMemory() => "prepend the memorized word".to_string(),
+        Rule::Memorize() => "memorize the word".to_string(),
+        Rule::RejectLonger(n) => format!("reject words longer than {}", n),
+        // JtR's `<0`, no word is shorter than 0 characters
+        Rule::RejectShorter(n) if *n > rule::MAX_LENGTH => "always reject".to_string(),
+        Rule::RejectShorter(n) => format!("reject words shorter than {}", n),
+        Rule::RejectNotLength(n) => format!("reject words not {} characters long", n),
+        Rule::RejectContains(class) => format!("reject words containing {}", class_name(class)),
+        Rule::RejectNotContains(class) => format!("reject words without {}", class_name(class)),
+        Rule::RejectNotFirst(class) => format!("reject words not starting with {}", class_name(class)),
+        Rule::RejectNotLast(class) => format!("reject words not ending with {}", class_name(class)),
+        Rule::RejectNotAt(n, class) => format!("reject words without {} at position {}", class_name(class), pos(0, *n)),
+        Rule::RejectFewer(n, class) => format!("reject words with fewer than {} {}", pos(0, *n), class_name(class)),
+        Rule::RejectUnchanged() => "reject words the line did not change".to_string(),
+        Rule::ReplaceClass(class, c) => format!("replace all {} with {}", class_name(class), quote(*c)),
+        Rule::PurgeClass(class) => format!("delete all {}", class_name(class)),
+        Rule::InsertString(n, s) if positions.is_none() && *n > rule::MAX_LENGTH => format!("append \"{}\"", s),
+        Rule::InsertString(n, s) => format!("insert \"{}\" at position {}", s, pos(0, *n)),
+        Rule::SetVariable(var, n, m) => format!("set {} to {} minus {}", variable(*var), position(n), position(m)),
+        Rule::Pluralize() => "make the word plural".to_string(),
+        Rule::Dynamic(inner, positions) => describe_with(inner, Some(positions)),
+        Rule::Invalid(e) => format!("invalid function ({})", e),
+    }
+}
+
+fn quote(c: char) -> String {
+    format!("'{}'", c)
+}
+
+fn variable(var: usize) -> String {
+    format!("variable {}", (b'a' + var as u8) as char)
+}
+
+fn position(pos: &Pos) -> String {
+    match pos {
+        Pos::Fixed(n) if *n > rule::MAX_LENGTH => "the end".to_string(),
+        Pos::Fixed(n) => n.to_string(),
+        Pos::Var(var) => variable(*var),
+        Pos::Length => "the word length".to_string(),
+        Pos::Memory => "the last position of the memorized word".to_string(),
+        Pos::Found => "the position last found".to_string(),
+    }
+}
+
+fn class_name(class: &Class) -> String {
+    let set = match class {
+        Class::Char(c) => return quote(*c),
+        Class::Set('?') => return quote('?'),
+        Class::Set('Z') => return "no characters".to_string(),
+        Class::Set(set) => set
+    };
+    let name = match set.to_ascii_lowercase() {
+        'v' => "vowels",
+        'c' => "consonants",
+        'w' => "whitespace",
+        'p' => "punctuation",
+        's' => "symbols",
+        'l' => "lowercase letters",
+        'u' => "uppercase letters",
+        'd' => "digits",
+        'a' => "letters",
+        'x' => "letters and digits",
+        'o' => "control characters",
+        'y' => "non-control characters",
+        _ => "characters"
+    };
+    match set.is_ascii_uppercase() {
+        true => format!("characters other than {}", name),
+        false => name.to_string()
+    }
+}
+
+/// Options shared with the other subcommands
+const SHARED: &[Shared] = &[Shared::Section];
+
+fn print_usage(program: &str) {
+    eprintln!("Usage: {} explain [options] <rule file>...", program);
+    eprintln!("Prints every rule line with a description of what it does.");
+    eprintln!("Options:");
+    eprintln!("  -e <rule line>                Explain a hashcat rule line instead of rule files.");
+    source::print_common_usage(SHARED);
+}
+
+pub fn main(program: &str, args: &[String]) {
+    let mut inline: Vec<String> = Vec::new();
+
+    let Options { paths, section, .. } = source::parse_args(program, args, print_usage, SHARED, |arg, args| {
+        match arg {
+            "-e" => inline.push(args.value("rule line", "-e <rule line>")),
+            _ => return false
+        }
+        true
+    });
+
+    if paths.is_empty() && inline.is_empty() {
+        print_usage(program);
+        std::process::exit(1);
+    }
+
+    let mut lines = source::read_files(&paths, section.as_deref());
+    for rule in &inline {
+        lines.extend(source::read_lines("-e", rule, None));
+    }
+    for line in &lines {
+        println!("{}\t{}", line.text, describe_source(line));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hashcat(line: &str) -> String {
+        describe_source(&source::read_lines("test.rule", line, None)[0])
+    }
+
+    fn jtr(line: &str) -> String {
+        describe_source(&source::read_lines("test.rule", &format!("[List.Rules:Test]\n{}\n", line), None)[0])
+    }
+
+    #[test]
+    fn functions() {
+        assert_eq!(hashcat(":"), "Do nothing");
+        assert_eq!(hashcat("c $1"), "Uppercase the first character, then append '1'");
+        assert_eq!(hashcat("l u t T3 r"), "Lowercase all letters, then uppercase all letters, then toggle the case of all letters, \
+            then toggle the case of the character at position 3, then reverse the word");
+        assert_eq!(hashcat("d p3 f { }"), "Duplicate the word, then repeat the word 3 times, then append the word reversed, \
+            then move the first character to the end, then move the last character to the front");
+        assert_eq!(hashcat("[ ] D2 x13 O02 i4! o0X '5"), "Delete the first character, then delete the last character, \
+            then delete the character at position 2, then keep only the 3 characters from position 1, \
+            then delete 2 characters from position 0, then insert '!' at position 4, \
+            then overwrite the character at position 0 with 'X', then keep only the first 5 characters");
+        assert_eq!(hashcat("sa@ @x z2 Z1 q"), "Replace every 'a' with '@', then delete every 'x', \
+            then prepend the first character 2 times, then append the last character 1 times, then double every character");
+        assert_eq!(hashcat("M X021 4 6"), "Memorize the word, then insert 2 characters of the memorized word from position 0 \
+            at position 1, then append the memorized word, then prepend the memorized word");
+    }
+
+    #[test]
+    fn rejections() {
+        assert_eq!(hashcat("<5 >2 _4"), "Reject words longer than 5, then reject words shorter than 2, \
+            then reject words not 4 characters long");
+        assert_eq!(hashcat("!a /b (c )d =1e %2f Q"), "Reject words containing 'a', then reject words without 'b', \
+            then reject words not starting with 'c', then reject words not ending with 'd', \
+            then reject words without 'e' at position 1, then reject words with fewer than 2 'f', \
+            then reject words the line did not change");
+        assert_eq!(jtr("<0"), "Always reject");
+        assert_eq!(jtr("<5"), "Reject words longer than 4");
+        assert_eq!(jtr("/?d !?V"), "Reject words without digits, then reject words containing characters other than vowels");
+    }
+
+    #[test]
+    fn jtr_functions() {
+        assert_eq!(jtr("s?vX @?d"), "Replace all vowels with 'X', then delete all digits");
+        assert_eq!(jtr("vbl1 Ab\"xy\""), "Set variable b to the word length minus 1, then insert \"xy\" at position variable b");
+        assert_eq!(jtr("Az\"!\" p"), "Append \"!\", then make the word plural");
+        assert_eq!(jtr("$[12]"), "Append '1' (first of 2 rule lines)");
+        assert_eq!(hashcat("k $1"), "Unsupported functions");
+    }
+}
@@ -0,0 +1,243 @@
+// Export of the compiled rules as JSON or YAML, for scripts and dashboards
+//
+// {"version": 1, "lines": [{"file": "best64.rule", "line": 3, "text": "c $1",
+//   "rules": [{"function": "Capitalize", "args": []}, {"function": "Append", "args": "1"}]}]}
+//
+// A template or JtR line expanding into several rule lines gets one entry per
+// rule line, all with the same file, line and text.
+
+use std::fs;
+
+use serde::{Deserialize, Serialize};
+
+use crate::jtr;
+use crate::rule::{self, Dialect, Pos, Rule};
+use crate::template;
+
+/// Version of the format, increased on incompatible changes
+const VERSION: u32 = 1;
+
+#[derive(Serialize, Deserialize)]
+pub struct RuleFile {
+    pub version: u32,
+    pub lines: Vec<RuleLine>,
+}
+
+/// A compiled rule line and where it came from
+#[derive(Serialize, Deserialize)]
+pub struct RuleLine {
+    pub file: String,
+    /// Line number in the file, starting at 1
+    pub line: usize,
+    pub text: String,
+    pub rules: Vec<Rule>,
+}
+
+/// Compiles the rule files read for a run, hashcat files first as they are
+/// applied in that order.
+pub fn compile_sources(sources: &[(String, String, Dialect)], expand: bool, section: Option<&str>, debug: bool) -> Vec<RuleLine> {
+    let mut out = Vec::new();
+    for (file, content, _) in sources.iter().filter(|(_, _, d)| *d == Dialect::Hashcat) {
+        out.extend(compile(file, content, Dialect::Hashcat, expand, section, debug));
+    }
+    for (file, content, _) in sources.iter().filter(|(_, _, d)| *d == Dialect::Jtr) {
+        out.extend(compile(file, content, Dialect::Jtr, expand, section, debug));
+    }
+    out
+}
+
+/// Compiles the rule file `path` line by line, keeping the source of every rule line.
+fn compile(path: &str, content: &str, dialect: Dialect, expand: bool, section: Option<&str>, debug: bool) -> Vec<RuleLine> {
+    let lines: Vec<(usize, &str)> = match dialect {
+        Dialect::Jtr => jtr::section_lines(content, section).into_iter().filter(|(_, l)| !jtr::is_comment(l)).collect(),
+        // Blank lines and comments are dropped by the parser
+        Dialect::Hashcat => content.lines().enumerate().collect()
+    };
+
+    let mut out = Vec::new();
+    for (i, text) in lines {
+        let compiled = match dialect {
+            Dialect::Jtr => jtr::compile_line(text),
+            Dialect::Hashcat if expand && text.starts_with('#') => Ok(Vec::new()),
+            Dialect::Hashcat if expand => template::expand_line(text, Dialect::Hashcat)
+                .map(|lines| lines.iter().flat_map(|l| parse_hashcat_line(l, debug)).collect()),
+            Dialect::Hashcat => Ok(parse_hashcat_line(text, debug))
+        };
+        match compiled {
+            Ok(lines) => out.extend(lines.into_iter().map(|rules| RuleLine {
+                file: path.to_string(),
+                line: i + 1,
+                text: text.strip_suffix('\r').unwrap_or(text).to_string(),
+                rules,
+            })),
+            Err(e) => {
+                if debug {
+                    eprintln!("[{}:{}] {} (line skipped)", path, i + 1, e);
+                }
+            }
+        }
+    }
+    out
+}
+
+/// Rule lines of a hashcat line as in the run: none for a blank line or a
+/// comment, one otherwise.
+fn parse_hashcat_line(text: &str, debug: bool) -> Vec<Vec<Rule>> {
+    crate::parse_rule_lines(text, debug)
+}
+
+/// Checks a rule line read from an export, which may have been edited
+fn validate(rules: &[Rule]) -> Result<(), String> {
+    let check_pos = |pos: &Pos| match pos {
+        Pos::Var(v) if *v >= rule::VARIABLES => Err(format!("Unknown variable {}", v)),
+        _ => Ok(())
+    };
+    for r in rules {
+        match r {
+            Rule::SetVariable(var, n, m) => {
+                check_pos(&Pos::Var(*var))?;
+                check_pos(n)?;
+                check_pos(m)?;
+            },
+            Rule::Dynamic(inner, positions) => {
+                match inner.position_count() {
+                    Some(count) if count == positions.len() => {},
+                    Some(count) => return Err(format!("{:?} takes {} positions, not {}", inner, count, positions.len())),
+                    None => return Err(format!("{:?} takes no positions", inner))
+                }
+                positions.iter().try_for_each(check_pos)?;
+            },
+            Rule::DuplicateN(n) | Rule::DuplicateFirstN(n) | Rule::DuplicateLastN(n) if *n > rule::MAX_LENGTH => {
+                return Err(format!("{:?} repeats more than {} times", r, rule::MAX_LENGTH));
+            },
+            // Lengths the parsers read, up to `+` or JtR's `z`
+            Rule::ExtractRange(_, n) | Rule::OmitRange(_, n) | Rule::ExtractMemory(_, n, _)
+                if *n > rule::MAX_LENGTH + 1 && *n != jtr::INFINITE => {
+                return Err(format!("{:?} spans more than {} characters", r, rule::MAX_LENGTH + 1));
+            },
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+/// YAML for `.yaml` and `.yml` files, JSON otherwise
+fn is_yaml(path: &str) -> bool {
+    path.ends_with(".yaml") || path.ends_with(".yml")
+}
+
+/// Writes `lines` to `path`, or stdout for `-`
+pub fn write(path: &str, lines: Vec<RuleLine>) -> Result<(), String> {
+    let file = RuleFile { version: VERSION, lines };
+    let mut out = if is_yaml(path) {
+        serde_yaml::to_string(&file).map_err(|e| e.to_string())?
+    } else {
+        serde_json::to_string_pretty(&file).map_err(|e| e.to_string())?
+    };
+    if !out.ends_with('\n') {
+        out.push('\n');
+    }
+
+    if path == "-" {
+        print!("{}", out);
+        Ok(())
+    } else {
+        fs::write(path, out).map_err(|e| e.to_string())
+    }
+}
+
+/// Reads the rule lines exported to `path`
+pub fn read(path: &str) -> Result<Vec<RuleLine>, String> {
+    let content = fs::read_to_string(path).map_err(|e| e.to_string())?;
+    parse(&content, is_yaml(path))
+}
+
+/// Rule lines of an export, checked before they are applied
+fn parse(content: &str, yaml: bool) -> Result<Vec<RuleLine>, String> {
+    let file: RuleFile = if yaml {
+        serde_yaml::from_str(content).map_err(|e| e.to_string())?
+    } else {
+        serde_json::from_str(content).map_err(|e| e.to_string())?
+    };
+    if file.version != VERSION {
+        return Err(format!("Unsupported version {}, expected {}", file.version, VERSION));
+    }
+    for line in &file.lines {
+        validate(&line.rules).map_err(|e| format!("{}:{}: {}", line.file, line.line, e))?;
+    }
+    Ok(file.lines)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HASHCAT: [&str; 3] = ["# best\n:\nc $1\n\n  # indented\nu\n", "r\r\nsa@", ""];
+    const JTR: &str = "[List.Rules:Test]\n# comment\nl\nAz\"[0-9]\"\n";
+
+    fn sources() -> Vec<(String, String, Dialect)> {
+        let mut sources: Vec<(String, String, Dialect)> = HASHCAT.iter().enumerate()
+            .map(|(i, c)| (format!("{}.rule", i), c.to_string(), Dialect::Hashcat))
+            .collect();
+        sources.insert(1, ("john.conf".to_string(), JTR.to_string(), Dialect::Jtr));
+        sources
+    }
+
+    /// Rule lines of a run with `-r` for every source, read like main does
+    fn run_rules(expand: bool) -> Vec<Vec<Rule>> {
+        let mut rule_string = String::new();
+        for content in HASHCAT {
+            rule_string.push_str(content);
+            rule_string.push('\n');
+        }
+        if expand {
+            rule_string = template::expand(&rule_string).unwrap().0;
+        }
+        let mut rules = crate::parse_rule_lines(&rule_string, false);
+        rules.extend(jtr::parse_rules(JTR, None, false));
+        rules
+    }
+
+    #[test]
+    fn import_matches_the_run() {
+        for expand in [false, true] {
+            let exported = compile_sources(&sources(), expand, None, false);
+            let json = serde_json::to_string(&RuleFile { version: VERSION, lines: exported }).unwrap();
+            let imported: Vec<Vec<Rule>> = parse(&json, false).unwrap().into_iter().map(|l| l.rules).collect();
+            let rules = run_rules(expand);
+            assert_eq!(imported, rules);
+
+            let candidates = |rules: &[Vec<Rule>]| -> Vec<Option<String>> {
+                rules.iter().map(|r| rule::apply_line(r, "pass")).collect()
+            };
+            assert_eq!(candidates(&imported), candidates(&rules));
+        }
+    }
+
+    #[test]
+    fn exported_lines_keep_their_source() {
+        let exported = compile_sources(&sources(), false, None, false);
+        let located: Vec<(&str, usize, &str)> = exported.iter().map(|l| (l.file.as_str(), l.line, l.text.as_str())).collect();
+        assert_eq!(located[..3], [("0.rule", 2, ":"), ("0.rule", 3, "c $1"), ("0.rule", 6, "u")]);
+        assert_eq!(located[3..5], [("1.rule", 1, "r"), ("1.rule", 2, "sa@")]);
+        assert_eq!(located[5].0, "john.conf");
+    }
+
+    #[test]
+    fn invalid_imports_are_rejected() {
+        let import = |rules: &str| parse(&format!(r#"{{"version": 1, "lines": [{{"file": "a", "line": 1, "text": "", "rules": {}}}]}}"#, rules), false);
+        assert!(import(r#"[{"function": "SetVariable", "args": [11, {"Fixed": 1}, {"Fixed": 0}]}]"#).is_err());
+        assert!(import(r#"[{"function": "SetVariable", "args": [0, {"Var": 20}, {"Fixed": 0}]}]"#).is_err());
+        assert!(import(r#"[{"function": "Dynamic", "args": [{"function": "Reverse", "args": []}, [{"Var": 0}]]}]"#).is_err());
+        assert!(import(r#"[{"function": "Dynamic", "args": [{"function": "ExtractRange", "args": [0, 0]}, [{"Var": 0}]]}]"#).is_err());
+        assert!(import(r#"[{"function": "Dynamic", "args": [{"function": "ExtractRange", "args": [0, 0]}, [{"Var": 0}, "Length"]]}]"#).is_ok());
+
+        // Counts are bounded like the text parsers bound them
+        assert!(import(r#"[{"function": "DuplicateN", "args": 1000000}]"#).is_err());
+        assert!(import(r#"[{"function": "DuplicateLastN", "args": 34}]"#).is_ok());
+        assert!(import(r#"[{"function": "ExtractRange", "args": [1, 18446744073709551615]}]"#).is_err());
+        assert!(import(r#"[{"function": "OmitRange", "args": [1, 127]}]"#).is_err());
+        let rules = import(&format!(r#"[{{"function": "ExtractRange", "args": [1, {}]}}]"#, jtr::INFINITE)).unwrap().swap_remove(0).rules;
+        assert_eq!(rule::apply_line(&rules, "word").unwrap(), "ord");
+    }
+}
@@ -0,0 +1,264 @@
+// `fmt` subcommand, rewrites hashcat rule files in a canonical form:
+// functions separated by a single space, upper case positions, no needless
+// escapes in rule templates and comments kept as they are.
+
+use std::fs;
+
+use crate::jtr;
+use crate::printer;
+use crate::rule::{Dialect, Rule};
+use crate::source::{self, Options, Shared};
+use crate::template;
+
+/// Options shared with the other subcommands
+const SHARED: &[Shared] = &[];
+
+fn print_usage(program: &str) {
+    eprintln!("Usage: {} fmt [options] <rule file>...", program);
+    eprintln!("Rewrites hashcat rule files in a canonical form and prints them.");
+    eprintln!("Options:");
+    eprintln!("  --in-place                    Rewrite the files instead of printing them.");
+    eprintln!("  --check                       Only list the files that are not formatted, exit with 1 if any.");
+    eprintln!("  --expand                      The files contain rule templates like $[0-9].");
+    eprintln!("  --collapse-blank              Remove leading and trailing blank lines and collapse runs of them.");
+    source::print_common_usage(SHARED);
+}
+
+pub fn main(program: &str, args: &[String]) {
+    let mut in_place = false;
+    let mut check = false;
+    let mut expand = false;
+    let mut collapse_blank = false;
+
+    let Options { paths, .. } = source::parse_args(program, args, print_usage, SHARED, |arg, _| {
+        match arg {
+            "--in-place" => in_place = true,
+            "--check" => check = true,
+            "--expand" => expand = true,
+            "--collapse-blank" => collapse_blank = true,
+            _ => return false
+        }
+        true
+    });
+
+    if paths.is_empty() {
+        print_usage(program);
+        std::process::exit(1);
+    }
+
+    let mut unformatted = false;
+    for path in paths {
+        let content = match fs::read_to_string(&path) {
+            Ok(content) => content,
+            Err(e) => {
+                eprintln!("Could not read file {}: {}", path, e);
+                std::process::exit(1);
+            }
+        };
+        if jtr::is_jtr(&content) {
+            eprintln!("{} is a JtR rule file, only hashcat rule files can be formatted.", path);
+            std::process::exit(1);
+        }
+
+        let formatted = format(&content, expand, collapse_blank, &path);
+        if check {
+            if formatted != content {
+                println!("{}", path);
+                unformatted = true;
+            }
+        } else if in_place {
+            if formatted != content {
+                if let Err(e) = fs::write(&path, &formatted) {
+                    eprintln!("Could not write file {}: {}", path, e);
+                    std::process::exit(1);
+                }
+            }
+        } else {
+            print!("{}", formatted);
+        }
+    }
+
+    if unformatted {
+        std::process::exit(1);
+    }
+}
+
+/// Canonical form of a rule file. Lines that cannot be formatted without
+/// changing their meaning are kept as they are. Blank lines are kept too,
+/// unless `collapse_blank` is set.
+pub fn format(content: &str, expand: bool, collapse_blank: bool, path: &str) -> String {
+    let mut out = String::new();
+    let mut blank = false;
+
+    for (i, line) in content.lines().enumerate() {
+        let line = line.strip_suffix('\r').unwrap_or(line);
+        if line.trim().is_empty() {
+            // Runs of blank lines become a single one
+            if collapse_blank {
+                blank = !out.is_empty();
+            } else {
+                out.push('\n');
+            }
+            continue;
+        }
+        if blank {
+            out.push('\n');
+            blank = false;
+        }
+
+        if line.starts_with('#') {
+            out.push_str(line.trim_end());
+        } else {
+            match format_line(line, expand) {
+                Some(formatted) => out.push_str(&formatted),
+                None => {
+                    eprintln!("{}:{}: could not format the line, kept as it is", path, i + 1);
+                    out.push_str(line);
+                }
+            }
+        }
+        out.push('\n');
+    }
+    out
+}
+
+/// Canonical form of a rule line, `None` if it cannot be formatted
+fn format_line(line: &str, expand: bool) -> Option<String> {
+    if expand && template::expand_line(line, Dialect::Hashcat).ok()? != [line] {
+        let formatted = layout(line, true)?;
+        return (expanded(&formatted)? == expanded(line)?).then_some(formatted);
+    }
+
+    let original = crate::parse_rules_checked(line, false).swap_remove(0);
+    // Lines the parser fully understands are printed from their rules,
+    // the others are laid out function by function
+    let formatted = if original.1.is_empty() {
+        let printed = printer::print_line(&original.0, Dialect::Hashcat).ok()?;
+        // Characters with a meaning in templates need an escape
+        match expand {
+            true => printed.replace('\\', "\\\\").replace('[', "\\[").replace('?', "\\?"),
+            false => printed
+        }
+    } else {
+        layout(line, expand)?
+    };
+
+    let text = match expand {
+        true => template::expand_line(&formatted, Dialect::Hashcat).ok()?.pop()?,
+        false => formatted.clone()
+    };
+    (crate::parse_rules_checked(&text, false).swap_remove(0) == original).then_some(formatted)
+}
+
+/// Parsed rule lines of a template line
+fn expanded(line: &str) -> Option<Vec<(Vec<Rule>, Vec<char>)>> {
+    let lines = template::expand_line(line, Dialect::Hashcat).ok()?;
+    Some(lines.iter().map(|l| crate::parse_rules_checked(l, false).swap_remove(0)).collect())
+}
+
+/// Arguments of a hashcat function, `N` for a position and `C` for a
+/// character. Includes the functions not implemented here.
+fn arguments(function: char) -> Option<&'static str> {
+    let args = match function {
+        ':' | 'l' | 'u' | 'c' | 'C' | 't' | 'r' | 'd' | 'f' | '{' | '}' | '[' | ']' | 'q' | 'k' | 'K' | 'E' | '4' | '6' | 'M' | 'Q' => "",
+        'T' | 'p' | 'D' | '\'' | 'z' | 'Z' | '<' | '>' | '_' | 'y' | 'Y' | 'L' | 'R' | '+' | '-' | '.' | ',' => "N",
+        '$' | '^' | '@' | '!' | '/' | '(' | ')' | 'e' => "C",
+        'x' | 'O' | '*' => "NN",
+        'i' | 'o' | '=' | '%' | '3' => "NC",
+        's' => "CC",
+        'X' => "NNN",
+        _ => return None
+    };
+    Some(args)
+}
+
+/// Lays a line out function by function, using the known number of
+/// arguments of every function. In templates an argument can be a class,
+/// a charset or an escaped character.
+fn layout(line: &str, expand: bool) -> Option<String> {
+    let chars: Vec<char> = line.chars().collect();
+    let mut i = 0;
+    let mut functions: Vec<String> = Vec::new();
+
+    while i < chars.len() {
+        if chars[i] == ' ' || chars[i] == '\t' {
+            i += 1;
+            continue;
+        }
+        // The function itself has to be a single character, not a choice
+        let (function, mut out) = match unit(&chars, &mut i, expand)? {
+            (Some(c), text) => (c, text),
+            (None, _) => return None
+        };
+        for kind in arguments(function)?.chars() {
+            let (c, text) = unit(&chars, &mut i, expand)?;
+            match (kind, c) {
+                ('N', Some(c)) if c.is_ascii_lowercase() => out.push(c.to_ascii_uppercase()),
+                _ => out.push_str(&text)
+            }
+        }
+        functions.push(out);
+    }
+    Some(functions.join(" "))
+}
+
+/// Reads a single character, or a template class or charset. Returns the
+/// character, if it is one, and its canonical text.
+fn unit(chars: &[char], i: &mut usize, expand: bool) -> Option<(Option<char>, String)> {
+    let c = *chars.get(*i)?;
+    *i += 1;
+    if !expand {
+        return Some((Some(c), c.to_string()));
+    }
+
+    match c {
+        '\\' => {
+            let escaped = *chars.get(*i)?;
+            *i += 1;
+            // Only characters with a meaning in templates need the escape
+            match escaped {
+                '[' | '\\' | '?' => Some((Some(escaped), format!("\\{}", escaped))),
+                _ => Some((Some(escaped), escaped.to_string()))
+            }
+        },
+        '?' => {
+            let set = *chars.get(*i)?;
+            *i += 1;
+            Some((None, format!("?{}", set)))
+        },
+        '[' => {
+            let mut out = String::from("[");
+            loop {
+                let c = *chars.get(*i)?;
+                *i += 1;
+                out.push(c);
+                match c {
+                    ']' => break,
+                    '\\' => {
+                        out.push(*chars.get(*i)?);
+                        *i += 1;
+                    },
+                    _ => {}
+                }
+            }
+            Some((None, out))
+        },
+        _ => Some((Some(c), c.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn blank_lines() {
+        let content = "\nc  $1\n\n\n# comment\nu\n\n";
+        let formatted = format(content, false, false, "test");
+        assert_eq!(formatted, "\nc $1\n\n\n# comment\nu\n\n");
+        assert_eq!(crate::parse_rule_lines(&formatted, false), crate::parse_rule_lines(content, false));
+        let collapsed = format(content, false, true, "test");
+        assert_eq!(collapsed, "c $1\n\n# comment\nu\n");
+        assert_eq!(crate::parse_rule_lines(&collapsed, false), crate::parse_rule_lines(content, false));
+    }
+}
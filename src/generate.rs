@@ -0,0 +1,253 @@
+// Random rule lines, like hashcat's -g/--generate-rules
+//
+// Every line gets between `func_min` and `func_max` functions, each one picked
+// with a probability proportional to its weight. Positions and counts are
+// 0-9 and 1-9, characters printable ASCII. The same seed and options always
+// give the same lines.
+
+use std::collections::HashSet;
+use std::fs;
+
+use crate::printer;
+use crate::random::Rng;
+use crate::rule::{Dialect, Rule};
+use crate::source::{self, Options, Shared};
+
+/// hashcat names of the functions lines are made of. Rejections and the
+/// memory functions are left out, as hashcat does.
+const FUNCTIONS: &[char] = &[
+    'l', 'u', 'c', 'C', 't', 'T', 'r', 'd', 'p', 'f', '{', '}', '$', '^', '[', ']',
+    'D', 'x', 'O', 'i', 'o', '\'', 's', '@', 'z', 'Z', 'q',
+];
+
+/// Lines and functions to generate, named after hashcat's options
+pub struct GenerateOptions {
+    pub func_min: usize,
+    pub func_max: usize,
+    /// Weight of every function in `FUNCTIONS`
+    pub weights: Vec<u64>,
+}
+
+impl Default for GenerateOptions {
+    fn default() -> GenerateOptions {
+        GenerateOptions {
+            func_min: 1,
+            func_max: 4,
+            weights: vec![1; FUNCTIONS.len()],
+        }
+    }
+}
+
+impl GenerateOptions {
+    /// Sets the weights listed in the file `path`, one `<function> <weight>`
+    /// per line, e.g. `$ 10`. Functions not listed keep their weight.
+    pub fn read_weights(&mut self, path: &str) -> Result<(), String> {
+        let content = fs::read_to_string(path).map_err(|e| e.to_string())?;
+        for (i, line) in content.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (function, weight) = match line.split_once(char::is_whitespace) {
+                Some((function, weight)) => (function, weight.trim()),
+                None => return Err(format!("line {}: expected <function> <weight>", i + 1))
+            };
+            let index = match function.chars().collect::<Vec<char>>().as_slice() {
+                [name] => FUNCTIONS.iter().position(|f| f == name),
+                _ => None
+            };
+            match (index, weight.parse::<u64>()) {
+                (Some(index), Ok(weight)) => self.weights[index] = weight,
+                (None, _) => return Err(format!("line {}: unknown function {}", i + 1, function)),
+                (_, Err(_)) => return Err(format!("line {}: invalid weight {}", i + 1, weight))
+            }
+            if self.total_weight().is_none() {
+                return Err(format!("line {}: the weights add up to more than {}", i + 1, u64::MAX));
+            }
+        }
+        Ok(())
+    }
+
+    /// Sum of the weights, `None` if it doesn't fit in a u64
+    fn total_weight(&self) -> Option<u64> {
+        self.weights.iter().try_fold(0u64, |sum, w| sum.checked_add(*w))
+    }
+
+    pub fn check(&self) -> Result<(), String> {
+        if self.func_min == 0 || self.func_min > self.func_max {
+            return Err(format!("Invalid number of functions {} to {}, the minimum must be at least 1 and at most the maximum.", self.func_min, self.func_max));
+        }
+        match self.total_weight() {
+            Some(0) => return Err("All function weights are 0.".to_string()),
+            None => return Err(format!("The function weights add up to more than {}.", u64::MAX)),
+            Some(_) => {}
+        }
+        Ok(())
+    }
+}
+
+/// `count` distinct random rule lines, fewer if the options don't allow
+/// that many
+pub fn generate(count: usize, options: &GenerateOptions, rng: &mut Rng) -> Vec<Vec<Rule>> {
+    let mut seen = HashSet::new();
+    let mut lines = Vec::with_capacity(count);
+
+    // Short lines run out of distinct combinations, stop trying at some point
+    let mut attempts = count.saturating_mul(100);
+    while lines.len() < count && attempts > 0 {
+        attempts -= 1;
+        let line = random_line(options, rng);
+        if let Ok(printed) = printer::print_line(&line, Dialect::Hashcat) {
+            if seen.insert(printed) {
+                lines.push(line);
+            }
+        }
+    }
+    lines
+}
+
+/// A random rule line of `func_min` to `func_max` functions
+pub fn random_line(options: &GenerateOptions, rng: &mut Rng) -> Vec<Rule> {
+    let functions = options.func_min + rng.below((options.func_max - options.func_min + 1) as u64) as usize;
+    (0..functions).map(|_| random_function(options, rng)).collect()
+}
+
+/// A random function, picked by the weights. The options must have passed `check`.
+pub fn random_function(options: &GenerateOptions, rng: &mut Rng) -> Rule {
+    let mut pick = rng.below(options.total_weight().expect("weights are checked"));
+    let index = options.weights.iter().position(|w| {
+        if pick < *w {
+            return true;
+        }
+        pick -= w;
+        false
+    }).unwrap();
+    function(FUNCTIONS[index], rng)
+}
+
+/// A random function named `name`
+fn function(name: char, rng: &mut Rng) -> Rule {
+    let mut pos = || rng.below(10) as usize;
+    let (n, m) = (pos(), pos());
+    let count = n.max(1);
+    let x = char_below(rng);
+    let y = char_below(rng);
+    match name {
+        'l' => Rule::Lowercase(),
+        'u' => Rule::Uppercase(),
+        'c' => Rule::Capitalize(),
+        'C' => Rule::InvertCapitalize(),
+        't' => Rule::ToggleCase(),
+        'T' => Rule::ToggleAt(n),
+        'r' => Rule::Reverse(),
+        'd' => Rule::Duplicate(),
+        'p' => Rule::DuplicateN(count),
+        'f' => Rule::Reflect(),
+        '{' => Rule::RotateLeft(),
+        '}' => Rule::RotateRight(),
+        '$' => Rule::Append(x),
+        '^' => Rule::Prepend(x),
+        '[' => Rule::TruncateLeft(),
+        ']' => Rule::TruncateRight(),
+        'D' => Rule::DeleteAt(n),
+        'x' => Rule::ExtractRange(n, m.max(1)),
+        'O' => Rule::OmitRange(n, m.max(1)),
+        'i' => Rule::InsertAt(n, x),
+        'o' => Rule::OverwriteAt(n, x),
+        '\'' => Rule::TruncateAt(count),
+        // Replacing a character with itself does nothing
+        's' if x == y => Rule::Replace(x, if y == '~' { ' ' } else { (y as u8 + 1) as char }),
+        's' => Rule::Replace(x, y),
+        '@' => Rule::Purge(x),
+        'z' => Rule::DuplicateFirstN(count),
+        'Z' => Rule::DuplicateLastN(count),
+        _ => Rule::DuplicateAll()
+    }
+}
+
+/// A random printable ASCII character
+fn char_below(rng: &mut Rng) -> char {
+    (b' ' + rng.below(95) as u8) as char
+}
+
+/// Options shared with the other subcommands
+const SHARED: &[Shared] = &[];
+
+fn print_usage(program: &str) {
+    eprintln!("Usage: {} generate-rules [options] <count>", program);
+    eprintln!("Prints <count> random rule lines.");
+    eprintln!("Options:");
+    eprintln!("  --func-min <n>                Minimum number of functions per rule line (default 1).");
+    eprintln!("  --func-max <n>                Maximum number of functions per rule line (default 4).");
+    eprintln!("  --weights <path>              Function weights, one <function> <weight> per line (default 1).");
+    eprintln!("  --seed <n>                    Seed of the random choices (default 0).");
+    source::print_common_usage(SHARED);
+}
+
+pub fn main(program: &str, args: &[String]) {
+    let mut options = GenerateOptions::default();
+    let mut seed: u64 = 0;
+
+    let Options { paths, .. } = source::parse_args(program, args, print_usage, SHARED, |arg, args| {
+        match arg {
+            "--func-min" => options.func_min = args.number(arg) as usize,
+            "--func-max" => options.func_max = args.number(arg) as usize,
+            "--seed" => seed = args.number(arg),
+            "--weights" => {
+                let path = args.value("weights location", "--weights <path>");
+                if let Err(e) = options.read_weights(&path) {
+                    eprintln!("Could not read weights {}: {}", path, e);
+                    std::process::exit(1);
+                }
+            },
+            _ => return false
+        }
+        true
+    });
+    let count: Option<usize> = match paths.as_slice() {
+        [count] => count.parse().ok(),
+        _ => None
+    };
+
+    let count = match count {
+        Some(count) => count,
+        None => {
+            print_usage(program);
+            std::process::exit(1);
+        }
+    };
+    if let Err(e) = options.check() {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    }
+
+    let lines = generate(count, &options, &mut Rng::new(seed));
+    for line in &lines {
+        // Only printable lines are generated
+        println!("{}", printer::print_line(line, Dialect::Hashcat).unwrap());
+    }
+    if lines.len() < count {
+        eprintln!("Only {} distinct rule lines could be generated.", lines.len());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn overflowing_weights_are_rejected() {
+        let path = std::env::temp_dir().join(format!("hcre-rs-weights-{}", std::process::id()));
+        fs::write(&path, format!("$ {}\n^ 1\n", u64::MAX)).unwrap();
+        let mut options = GenerateOptions::default();
+        let result = options.read_weights(path.to_str().unwrap());
+        fs::remove_file(&path).unwrap();
+        assert!(result.unwrap_err().starts_with("line 1:"));
+
+        let mut options = GenerateOptions { weights: vec![0; FUNCTIONS.len()], ..GenerateOptions::default() };
+        options.weights[0] = u64::MAX;
+        assert!(options.check().is_ok());
+        options.weights[1] = 1;
+        assert!(options.check().is_err());
+    }
+}
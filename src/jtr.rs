@@ -0,0 +1,331 @@
+// John the Ripper rule syntax
+// see https://www.openwall.com/john/doc/RULES.shtml
+//
+// Functions JtR shares with hashcat are parsed into the same `Rule` values,
+// only character classes, variable positions and JtR specific commands get
+// their own variants.
+
+use crate::rule::{self, Class, Dialect, Pos, Rule};
+use crate::string_reader::StringReader;
+use crate::template;
+
+/// Whether `content` looks like a JtR rule file, i.e. has `[List.Rules:...]` sections
+pub fn is_jtr(content: &str) -> bool {
+    content.lines().any(|l| l.trim_start().to_lowercase().starts_with("[list.rules:"))
+}
+
+/// Compiles a JtR rule file into rule lines. Only the `[List.Rules:<section>]`
+/// sections are used, all of them if `section` is `None`. Files without any
+/// section headers are used as a whole. Lines that can't be compiled are
+/// skipped, listed with `debug` and counted on stderr otherwise.
+pub fn parse_rules(content: &str, section: Option<&str>, debug: bool) -> Vec<Vec<Rule>> {
+    let mut rules = Vec::new();
+    let mut skipped = 0;
+    for (i, line) in section_lines(content, section) {
+        if is_comment(line) {
+            continue;
+        }
+        match compile_line(line) {
+            Ok(lines) => rules.extend(lines),
+            Err(e) => {
+                skipped += 1;
+                if debug {
+                    eprintln!("[line {}] {} (line skipped)", i + 1, e);
+                }
+            }
+        }
+    }
+    if skipped > 0 && !debug {
+        eprintln!("Skipped {} JtR rule lines that are not supported, -d lists them.", skipped);
+    }
+    rules
+}
+
+/// The lines of the selected sections, with their index in `content`
+pub fn section_lines<'a>(content: &'a str, section: Option<&str>) -> Vec<(usize, &'a str)> {
+    let has_sections = content.lines().any(|l| section_header(l).is_some());
+    let mut in_section = !has_sections;
+    let mut lines = Vec::new();
+
+    for (i, line) in content.lines().enumerate() {
+        if let Some(header) = section_header(line) {
+            in_section = match header.strip_prefix("list.rules:") {
+                Some(name) => section.is_none_or(|s| s.eq_ignore_ascii_case(name)),
+                None => false
+            };
+            continue;
+        }
+        if in_section {
+            lines.push((i, line));
+        }
+    }
+    lines
+}
+
+/// The lower case name of a section header like `[List.Rules:Wordlist]` or
+/// `[Options]`. Rule lines like `[lu]$[0-9]` start with `[` too, so only the
+/// sections of john.conf are headers.
+pub fn section_header(line: &str) -> Option<String> {
+    // Headers are never indented
+    let header = line.trim_end().strip_prefix('[')?.strip_suffix(']')?.to_lowercase();
+    let kind = header.split(':').next().unwrap_or_default();
+    let known = kind.starts_with("list.")
+        || ["options", "incremental", "markov", "local", "debug", "disabled", "subsets", "regen", "prince"].contains(&kind);
+    (known && !header.contains(' ')).then_some(header)
+}
+
+/// Whether a line of a rule section is blank or a comment
+pub fn is_comment(line: &str) -> bool {
+    let trimmed = line.trim();
+    trimmed.is_empty() || trimmed.starts_with('#') || trimmed.starts_with(';')
+}
+
+/// Preprocesses and compiles a rule line, into one or more rule lines
+pub fn compile_line(line: &str) -> Result<Vec<Vec<Rule>>, String> {
+    if line.trim_start().starts_with('.') {
+        return Err(format!("Directive {} not supported", line.trim()));
+    }
+    template::expand_line(line, Dialect::Jtr)?
+        .iter()
+        .map(|l| parse_line(l).map_err(|e| format!("{} in {}", e, l)))
+        .collect()
+}
+
+/// Position `z`, "infinite", beyond the end of any word
+pub const INFINITE: usize = usize::MAX / 4;
+
+/// A position: `0`-`9` and `A`-`Z`, the length constants `*`, `-`, `+` and `z`,
+/// or one that is only known when applying the rule.
+fn read_pos(reader: &mut StringReader) -> Result<Pos, String> {
+    let c = reader.read().map_err(|_| "Missing position")?;
+    let pos = match c {
+        '0'..='9' => Pos::Fixed(c as usize - '0' as usize),
+        'A'..='Z' => Pos::Fixed(c as usize - 'A' as usize + 10),
+        '*' => Pos::Fixed(rule::MAX_LENGTH),
+        '-' => Pos::Fixed(rule::MAX_LENGTH - 1),
+        '+' => Pos::Fixed(rule::MAX_LENGTH + 1),
+        'z' => Pos::Fixed(INFINITE),
+        'a'..='k' => Pos::Var(c as usize - 'a' as usize),
+        'l' => Pos::Length,
+        'm' => Pos::Memory,
+        'p' => Pos::Found,
+        _ => return Err(format!("Invalid position {}", c))
+    };
+    Ok(pos)
+}
+
+fn read_char(reader: &mut StringReader) -> Result<char, String> {
+    reader.read().map_err(|_| "Missing character".to_string())
+}
+
+/// A character, or a character class like `?v`
+fn read_class(reader: &mut StringReader) -> Result<Class, String> {
+    match read_char(reader)? {
+        '?' => {
+            let set = read_char(reader)?;
+            if set == '?' {
+                Ok(Class::Char('?'))
+            } else if Class::is_set(set) {
+                Ok(Class::Set(set))
+            } else {
+                Err(format!("Unknown character class ?{}", set))
+            }
+        },
+        c => Ok(Class::Char(c))
+    }
+}
+
+/// `rule` with its positions set, deferred to when it is applied if any of them is variable.
+fn positional(rule: Rule, positions: Vec<Pos>) -> Rule {
+    let fixed: Vec<usize> = positions.iter().filter_map(|p| match p {
+        Pos::Fixed(n) => Some(*n),
+        _ => None
+    }).collect();
+
+    if fixed.len() == positions.len() {
+        rule.with_positions(&fixed)
+    } else {
+        Rule::Dynamic(Box::new(rule), positions)
+    }
+}
+
+/// A length that has to be known when parsing
+fn fixed(pos: Pos) -> Result<usize, String> {
+    match pos {
+        Pos::Fixed(n) => Ok(n),
+        _ => Err("Variable lengths are not supported here".to_string())
+    }
+}
+
+/// Compiles a single, already preprocessed, JtR rule line
+fn parse_line(line: &str) -> Result<Vec<Rule>, String> {
+    let mut reader = StringReader::from_string(line);
+    let mut rules = Vec::new();
+
+    while let Ok(c) = reader.read() {
+        let rule = match c {
+            ':' => Rule::Nothing,
+            'l' => Rule::Lowercase(),
+            'u' => Rule::Uppercase(),
+            'c' => Rule::Capitalize(),
+            'C' => Rule::InvertCapitalize(),
+            't' => Rule::ToggleCase(),
+            'r' => Rule::Reverse(),
+            'd' => Rule::Duplicate(),
+            'f' => Rule::Reflect(),
+            '{' => Rule::RotateLeft(),
+            '}' => Rule::RotateRight(),
+            '[' => Rule::TruncateLeft(),
+            ']' => Rule::TruncateRight(),
+            'p' => Rule::Pluralize(),
+            'M' => Rule::Memorize(),
+            'Q' => Rule::RejectUnchanged(),
+            '4' => Rule::AppendMemory(),
+            '6' => Rule::PrependMemory(),
+            '$' => Rule::Append(read_char(&mut reader)?),
+            '^' => Rule::Prepend(read_char(&mut reader)?),
+            'T' => positional(Rule::ToggleAt(0), vec![read_pos(&mut reader)?]),
+            'D' => positional(Rule::DeleteAt(0), vec![read_pos(&mut reader)?]),
+            '\'' => positional(Rule::TruncateAt(0), vec![read_pos(&mut reader)?]),
+            'x' => positional(Rule::ExtractRange(0, 0), vec![read_pos(&mut reader)?, read_pos(&mut reader)?]),
+            'O' => positional(Rule::OmitRange(0, 0), vec![read_pos(&mut reader)?, read_pos(&mut reader)?]),
+            'i' => match (read_pos(&mut reader)?, read_char(&mut reader)?) {
+                // `iz` inserts past the end of any word
+                (Pos::Fixed(n), c) if n > rule::MAX_LENGTH => Rule::Append(c),
+                (pos, c) => positional(Rule::InsertAt(0, c), vec![pos])
+            },
+            'o' => {
+                let pos = read_pos(&mut reader)?;
+                positional(Rule::OverwriteAt(0, read_char(&mut reader)?), vec![pos])
+            },
+            'X' => positional(Rule::ExtractMemory(0, 0, 0), vec![read_pos(&mut reader)?, read_pos(&mut reader)?, read_pos(&mut reader)?]),
+            's' => {
+                match read_class(&mut reader)? {
+                    Class::Char(x) => Rule::Replace(x, read_char(&mut reader)?),
+                    class => Rule::ReplaceClass(class, read_char(&mut reader)?)
+                }
+            },
+            '@' => {
+                match read_class(&mut reader)? {
+                    Class::Char(x) => Rule::Purge(x),
+                    class => Rule::PurgeClass(class)
+                }
+            },
+            '!' => Rule::RejectContains(read_class(&mut reader)?),
+            '/' => Rule::RejectNotContains(read_class(&mut reader)?),
+            '(' => Rule::RejectNotFirst(read_class(&mut reader)?),
+            ')' => Rule::RejectNotLast(read_class(&mut reader)?),
+            '=' => {
+                let pos = read_pos(&mut reader)?;
+                positional(Rule::RejectNotAt(0, read_class(&mut reader)?), vec![pos])
+            },
+            '%' => {
+                let pos = read_pos(&mut reader)?;
+                positional(Rule::RejectFewer(0, read_class(&mut reader)?), vec![pos])
+            },
+            // JtR rejects unless shorter / longer than N, hashcat unless not longer / shorter
+            '<' => match fixed(read_pos(&mut reader)?)? {
+                0 => Rule::RejectShorter(usize::MAX),
+                n => Rule::RejectLonger(n - 1),
+            },
+            '>' => Rule::RejectShorter(fixed(read_pos(&mut reader)?)? + 1),
+            '_' => Rule::RejectNotLength(fixed(read_pos(&mut reader)?)?),
+            'A' => {
+                let pos = read_pos(&mut reader)?;
+                let delimiter = read_char(&mut reader)?;
+                let mut s = String::new();
+                loop {
+                    match reader.read() {
+                        Ok(c) if c == delimiter => break,
+                        Ok(c) => s.push(c),
+                        Err(_) => return Err("Unterminated string of A".to_string())
+                    }
+                }
+                positional(Rule::InsertString(0, s), vec![pos])
+            },
+            'v' => {
+                let var = match read_char(&mut reader)? {
+                    v @ 'a'..='k' => v as usize - 'a' as usize,
+                    v => return Err(format!("Invalid variable {}", v))
+                };
+                Rule::SetVariable(var, read_pos(&mut reader)?, read_pos(&mut reader)?)
+            },
+            // Flags rejecting rules for some hash types, every rule applies here
+            '-' => {
+                match read_char(&mut reader)? {
+                    '<' | '>' => { read_pos(&mut reader)?; },
+                    ':' | 'c' | '8' | 's' | 'p' | 'u' | 'U' => {},
+                    f => return Err(format!("Unknown flag -{}", f))
+                }
+                continue;
+            },
+            ' ' | '\t' => continue,
+            _ => return Err(format!("Command {} not supported", c))
+        };
+        rules.push(rule);
+    }
+    Ok(rules)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn section_headers() {
+        let content = "[Options]\nu\n[List.Rules:A]\n[lu]$[0-9]\n:\n[List.Rules:B]\nr\n[Incremental:ASCII]\nd\n";
+        let lines = |section| section_lines(content, section).into_iter().map(|(i, l)| (i, l.to_string())).collect::<Vec<_>>();
+        assert_eq!(lines(Some("a")), vec![(3, "[lu]$[0-9]".to_string()), (4, ":".to_string())]);
+        assert_eq!(lines(Some("B")), vec![(6, "r".to_string())]);
+        assert_eq!(lines(None).len(), 3);
+        assert_eq!(parse_rules(content, Some("A"), false).len(), 21);
+    }
+
+    const RULES: &str = "\
+[List.Rules:Lengths]
+<5 $1
+>3 ^x
+_4 r
+<0 $0
+
+[List.Rules:Commands]
+/?d ]
+%2?d $!
+-c c
+-:u
+-<8 -s $2
+vbl1 Ab\"xy\"
+Az\"!\"
+va31 Da
+";
+
+    /// What every rule line of `section` makes of `word`
+    fn apply(section: &str, word: &str) -> Vec<Option<String>> {
+        parse_rules(RULES, Some(section), false).iter().map(|l| rule::apply_line(l, word)).collect()
+    }
+
+    fn some(candidates: &[&str]) -> Vec<Option<String>> {
+        candidates.iter().map(|c| (!c.is_empty()).then(|| c.to_string())).collect()
+    }
+
+    #[test]
+    fn length_rejections() {
+        assert_eq!(apply("Lengths", "abcd"), some(&["abcd1", "xabcd", "dcba", ""]));
+        assert_eq!(apply("Lengths", "abc"), some(&["abc1", "", "", ""]));
+        assert_eq!(apply("lengths", "abcde"), some(&["", "xabcde", "", ""]));
+    }
+
+    #[test]
+    fn commands() {
+        assert_eq!(apply("Commands", "ab12"), some(&["ab1", "ab12!", "Ab12", "AB12", "ab122", "ab1xy2", "ab12!", "ab2"]));
+        assert_eq!(apply("Commands", "abc"), some(&["", "", "Abc", "ABC", "abc2", "abxyc", "abc!", "ab"]));
+    }
+
+    #[test]
+    fn sections() {
+        assert_eq!(parse_rules(RULES, Some("Lengths"), false).len(), 4);
+        assert_eq!(parse_rules(RULES, Some("Commands"), false).len(), 8);
+        assert_eq!(parse_rules(RULES, None, false).len(), 12);
+        assert!(parse_rules(RULES, Some("Missing"), false).is_empty());
+    }
+}
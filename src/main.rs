@@ -1,21 +1,183 @@
-use std::fmt::Debug;
-use std::io::{self, BufRead};
-use std::collections::HashSet;
 use std::env;
 use std::fs;
+use std::io;
+use std::sync::atomic::AtomicBool;
+use std::sync::Arc;
+use std::time::Duration;
 
+mod analyze;
+mod combinator;
+mod convert;
+mod cover;
+mod dedupe;
+mod diff;
+mod evolve;
+mod explain;
+mod export;
+mod format;
+mod generate;
+mod hyperloglog;
+mod jtr;
+mod mask;
+mod prince;
+mod printer;
+mod random;
+mod rank;
+mod repl;
+mod report;
+mod restore;
 mod rule;
+mod rulegen;
+mod run;
+mod simplify;
+mod source;
+mod stats;
 mod string_reader;
-use rule::Rule;
+mod template;
+mod wordlist;
+use combinator::Combinator;
+use generate::GenerateOptions;
+use mask::{Mask, MaskList};
+use prince::{Prince, PrinceOptions};
+use restore::Restore;
+use rule::{Class, Dialect, Rule};
+use run::{Finished, Hybrid, RunOptions};
+use stats::Stats;
 use string_reader::StringReader;
+use wordlist::Wordlist;
+
+/// Default location of the restore file when `--restore` is used without `--restore-file-path`
+const DEFAULT_RESTORE_PATH: &str = "hcre-rs.restore";
+
+fn print_usage(program: &str) {
+    eprintln!("Usage: {} [options] [mask | left wordlist right wordlist]", program);
+    eprintln!("       {} convert [options] <rule file>", program);
+    eprintln!("       {} fmt [options] <rule file>...", program);
+    eprintln!("       {} simplify [options] <rule file>", program);
+    eprintln!("       {} dedupe-rules [options] <rule file>...", program);
+    eprintln!("       {} rank [options] <rule file>...", program);
+    eprintln!("       {} cover [options] <rule file>...", program);
+    eprintln!("       {} rulegen [options] <word> <password>", program);
+    eprintln!("       {} generate-rules [options] <count>", program);
+    eprintln!("       {} evolve [options] [rule file]...", program);
+    eprintln!("       {} analyze [options] <rule file>...", program);
+    eprintln!("       {} diff [options] <old rule file> <new rule file>", program);
+    eprintln!("       {} explain [options] <rule file>...", program);
+    eprintln!("       {} report [options] <rule file>...", program);
+    eprintln!("       {} repl [options] [word]", program);
+    eprintln!("Options:");
+    eprintln!("  -r <path to rule file>        Specify a rule file to use.");
+    eprintln!("  -w <path to wordlist>         Read words from a file instead of stdin.");
+    eprintln!("  --expand                      Expand rule templates like $[0-9] or s[ae][@4] in the rule files.");
+    eprintln!("  --simplify                    Simplify every rule line before applying it, e.g. `l u` to `u`.");
+    eprintln!("  --jtr-rules <path>            Specify a John the Ripper rule file to use. Files passed with -r");
+    eprintln!("                                are read as JtR rules if they have [List.Rules:...] sections.");
+    eprintln!("  --jtr-section <name>          Only use the [List.Rules:<name>] section of JtR rule files.");
+    eprintln!("  --export-rules <path>         Write the compiled rules with their source lines as JSON, or YAML");
+    eprintln!("                                for .yaml/.yml files, and exit. Use - for stdout.");
+    eprintln!("  --import-rules <path>         Use rules exported with --export-rules.");
+    eprintln!("  -a <mode>                     Attack mode: 0 wordlist (default), 1 combinator, 3 mask,");
+    eprintln!("                                6 wordlist + mask, 7 mask + wordlist.");
+    eprintln!("  -j <rule>                     Rule applied to the left words in combinator mode.");
+    eprintln!("  -k <rule>                     Rule applied to the right words in combinator mode.");
+    eprintln!("  --separator <string>          Put <string> between the words in combinator mode.");
+    eprintln!("  -1, -2, -3, -4 <charset>      Custom charsets ?1 to ?4 for the mask.");
+    eprintln!("  --prince                      Chain 1..N words of the wordlist (PRINCE), then apply the rules.");
+    eprintln!("  --elem-cnt-min <n>            Minimum number of words per chain (default 1).");
+    eprintln!("  --elem-cnt-max <n>            Maximum number of words per chain (default 8).");
+    eprintln!("  --pw-min <n>                  Minimum length of a chain (default 1).");
+    eprintln!("  --pw-max <n>                  Maximum length of a chain (default 16).");
+    eprintln!("  --increment                   Use the first 1 to all positions of the mask.");
+    eprintln!("  --increment-min <n>           Start increment mode at <n> positions.");
+    eprintln!("  --increment-max <n>           Stop increment mode at <n> positions.");
+    eprintln!("  --restore                     Continue an interrupted run from the restore file.");
+    eprintln!("  --restore-file-path <path>    Periodically write a restore file to <path>.");
+    eprintln!("  --restore-timer <seconds>     Seconds between restore file updates (default 10).");
+    eprintln!("  -s, --skip <n>                Skip the first <n> words x rule lines of the keyspace.");
+    eprintln!("  -l, --limit <n>               Stop after <n> words x rule lines of the keyspace.");
+    eprintln!("  --max-candidates <n>          Stop after emitting <n> candidates and write a restore file.");
+    eprintln!("  --max-time <duration>         Stop after <duration> (e.g. 90s, 30m, 2h) and write a restore file.");
+    eprintln!("  --sample-rules <k>            Apply only <k> randomly chosen rule lines to every word.");
+    eprintln!("  --seed <n>                    Seed for --sample-rules and --generate-rules (default 0).");
+    eprintln!("  -g, --generate-rules <n>      Apply <n> random rule lines, after the ones of the rule files.");
+    eprintln!("  --generate-rules-func-min <n> Minimum number of functions per random rule line (default 1).");
+    eprintln!("  --generate-rules-func-max <n> Maximum number of functions per random rule line (default 4).");
+    eprintln!("  --generate-rules-weights <path>");
+    eprintln!("                                Function weights, one <function> <weight> per line (default 1).");
+    eprintln!("  --loopback <n>                Feed new candidates back through the rules <n> times.");
+    eprintln!("  --rule-major                  Apply each rule line to all words before the next rule line.");
+    eprintln!("                                Candidates are not deduplicated in this order.");
+    eprintln!("  --status                      Show a status line on stderr and a summary at the end.");
+    eprintln!("  --status-timer <seconds>      Seconds between status line updates (default 1).");
+    eprintln!("  --keyspace                    Print the size of the keyspace (words x rule lines) and exit.");
+    eprintln!("  -d                            Enable debug output.");
+    eprintln!("  -h                            Display this help message.");
+}
 
 fn main() {
     let mut debug = false;
 
     // Compiling all the rules
     let mut rule_paths: Vec<String> = Vec::new();
-    
+    let mut wordlist_path: Option<String> = None;
+    let mut restore = false;
+    let mut restore_path: Option<String> = None;
+    let mut restore_timer: u64 = 10;
+    let mut skip: u64 = 0;
+    let mut limit: Option<u64> = None;
+    let mut keyspace = false;
+    let mut status = false;
+    let mut status_timer: u64 = 1;
+    let mut max_candidates: Option<u64> = None;
+    let mut max_time: Option<Duration> = None;
+    let mut sample_rules: Option<usize> = None;
+    let mut seed: u64 = 0;
+    let mut generate_rules: Option<usize> = None;
+    let mut generate_options = GenerateOptions::default();
+    let mut rule_major = false;
+    let mut loopback: usize = 0;
+    let mut attack_mode: u8 = 0;
+    let mut positionals: Vec<String> = Vec::new();
+    let mut left_rule = String::new();
+    let mut right_rule = String::new();
+    let mut separator = String::new();
+    let mut expand = false;
+    let mut simplify = false;
+    let mut jtr_paths: Vec<String> = Vec::new();
+    let mut jtr_section: Option<String> = None;
+    let mut export_path: Option<String> = None;
+    let mut import_paths: Vec<String> = Vec::new();
+    let mut prince = false;
+    let mut prince_options = PrinceOptions::default();
+    let mut custom_charsets: [Option<Vec<u8>>; 4] = [None, None, None, None];
+    let mut increment = false;
+    let mut increment_min: usize = 1;
+    let mut increment_max: usize = usize::MAX;
+
     let args: Vec<String> = env::args().collect();
+    // Subcommands have their own options
+    let subcommand: Option<fn(&str, &[String])> = match args.get(1).map(|a| a.as_str()) {
+        Some("convert") => Some(convert::main),
+        Some("fmt") => Some(format::main),
+        Some("simplify") => Some(simplify::main),
+        Some("dedupe-rules") => Some(dedupe::main),
+        Some("rank") => Some(rank::main),
+        Some("cover") => Some(cover::main),
+        Some("rulegen") => Some(rulegen::main),
+        Some("generate-rules") => Some(generate::main),
+        Some("evolve") => Some(evolve::main),
+        Some("analyze") => Some(analyze::main),
+        Some("diff") => Some(diff::main),
+        Some("explain") => Some(explain::main),
+        Some("report") => Some(report::main),
+        Some("repl") => Some(repl::main),
+        _ => None
+    };
+    if let Some(subcommand) = subcommand {
+        subcommand(&args[0], &args[2..]);
+        return;
+    }
+
     let mut i: usize = 1;
     loop {
         if i >= args.len() {
@@ -31,17 +193,309 @@ fn main() {
 
                 rule_paths.push(args[i].clone());
             },
+            "-w" => {
+                i += 1;
+                if i >= args.len() {
+                    eprintln!("Missing wordlist location. Usage: -w <path to wordlist>.");
+                    std::process::exit(1);
+                }
+
+                wordlist_path = Some(args[i].clone());
+            },
+            "--restore" => {
+                restore = true;
+            },
+            "--restore-file-path" => {
+                i += 1;
+                if i >= args.len() {
+                    eprintln!("Missing restore file location. Usage: --restore-file-path <path>.");
+                    std::process::exit(1);
+                }
+
+                restore_path = Some(args[i].clone());
+            },
+            "--restore-timer" => {
+                i += 1;
+                match args.get(i).and_then(|a| a.parse().ok()) {
+                    Some(t) => restore_timer = t,
+                    None => {
+                        eprintln!("Missing or invalid restore timer. Usage: --restore-timer <seconds>.");
+                        std::process::exit(1);
+                    }
+                }
+            },
+            "-s" | "--skip" => {
+                i += 1;
+                match args.get(i).and_then(|a| a.parse().ok()) {
+                    Some(s) => skip = s,
+                    None => {
+                        eprintln!("Missing or invalid skip. Usage: -s <n>.");
+                        std::process::exit(1);
+                    }
+                }
+            },
+            "-l" | "--limit" => {
+                i += 1;
+                match args.get(i).and_then(|a| a.parse().ok()) {
+                    Some(l) => limit = Some(l),
+                    None => {
+                        eprintln!("Missing or invalid limit. Usage: -l <n>.");
+                        std::process::exit(1);
+                    }
+                }
+            },
+            "--keyspace" => {
+                keyspace = true;
+            },
+            "--loopback" => {
+                i += 1;
+                match args.get(i).and_then(|a| a.parse().ok()) {
+                    Some(n) => loopback = n,
+                    None => {
+                        eprintln!("Missing or invalid loopback count. Usage: --loopback <n>.");
+                        std::process::exit(1);
+                    }
+                }
+            },
+            "--rule-major" => {
+                rule_major = true;
+            },
+            "--status" => {
+                status = true;
+            },
+            "--status-timer" => {
+                i += 1;
+                match args.get(i).and_then(|a| a.parse().ok()) {
+                    Some(t) => status_timer = t,
+                    None => {
+                        eprintln!("Missing or invalid status timer. Usage: --status-timer <seconds>.");
+                        std::process::exit(1);
+                    }
+                }
+            },
+            "--max-candidates" => {
+                i += 1;
+                match args.get(i).and_then(|a| a.parse().ok()) {
+                    Some(n) => max_candidates = Some(n),
+                    None => {
+                        eprintln!("Missing or invalid candidate budget. Usage: --max-candidates <n>.");
+                        std::process::exit(1);
+                    }
+                }
+            },
+            "--max-time" => {
+                i += 1;
+                match args.get(i).and_then(|a| parse_duration(a)) {
+                    Some(d) => max_time = Some(d),
+                    None => {
+                        eprintln!("Missing or invalid time budget. Usage: --max-time <duration>, e.g. 90s, 30m or 2h.");
+                        std::process::exit(1);
+                    }
+                }
+            },
+            "--sample-rules" => {
+                i += 1;
+                match args.get(i).and_then(|a| a.parse().ok()) {
+                    Some(k) => sample_rules = Some(k),
+                    None => {
+                        eprintln!("Missing or invalid sample size. Usage: --sample-rules <k>.");
+                        std::process::exit(1);
+                    }
+                }
+            },
+            "--seed" => {
+                i += 1;
+                match args.get(i).and_then(|a| a.parse().ok()) {
+                    Some(s) => seed = s,
+                    None => {
+                        eprintln!("Missing or invalid seed. Usage: --seed <n>.");
+                        std::process::exit(1);
+                    }
+                }
+            },
+            "-g" | "--generate-rules" => {
+                i += 1;
+                match args.get(i).and_then(|a| a.parse().ok()) {
+                    Some(n) => generate_rules = Some(n),
+                    None => {
+                        eprintln!("Missing or invalid number of rule lines. Usage: --generate-rules <n>.");
+                        std::process::exit(1);
+                    }
+                }
+            },
+            "--generate-rules-func-min" | "--generate-rules-func-max" => {
+                let option = args[i].clone();
+                i += 1;
+                let n = match args.get(i).and_then(|a| a.parse().ok()) {
+                    Some(n) => n,
+                    None => {
+                        eprintln!("Missing or invalid value. Usage: {} <n>.", option);
+                        std::process::exit(1);
+                    }
+                };
+                match option.as_str() {
+                    "--generate-rules-func-min" => generate_options.func_min = n,
+                    _ => generate_options.func_max = n,
+                }
+            },
+            "--generate-rules-weights" => {
+                i += 1;
+                if i >= args.len() {
+                    eprintln!("Missing weights location. Usage: --generate-rules-weights <path>.");
+                    std::process::exit(1);
+                }
+
+                if let Err(e) = generate_options.read_weights(&args[i]) {
+                    eprintln!("Could not read weights {}: {}", args[i], e);
+                    std::process::exit(1);
+                }
+            },
             "-h" => {
-                eprintln!("Usage: {} [options]", args[0]);
-                eprintln!("Options:");
-                eprintln!("  -r <path to rule file>    Specify a rule file to use.");
-                eprintln!("  -h                        Display this help message.");
+                print_usage(&args[0]);
                 std::process::exit(0);
             },
+            "-a" => {
+                i += 1;
+                match args.get(i).map(|a| a.as_str()) {
+                    Some("0") => attack_mode = 0,
+                    Some("1") => attack_mode = 1,
+                    Some("3") => attack_mode = 3,
+                    Some("6") => attack_mode = 6,
+                    Some("7") => attack_mode = 7,
+                    _ => {
+                        eprintln!("Missing or unsupported attack mode. Usage: -a <0|1|3|6|7>.");
+                        std::process::exit(1);
+                    }
+                }
+            },
+            "-1" | "-2" | "-3" | "-4" => {
+                let n = args[i][1..].parse::<usize>().unwrap();
+                i += 1;
+                if i >= args.len() {
+                    eprintln!("Missing charset. Usage: -{} <charset>.", n);
+                    std::process::exit(1);
+                }
+                match mask::parse_charset(&args[i]) {
+                    Ok(charset) => custom_charsets[n - 1] = Some(charset),
+                    Err(e) => {
+                        eprintln!("{}", e);
+                        std::process::exit(1);
+                    }
+                }
+            },
+            "-j" | "-k" => {
+                let side = args[i].clone();
+                i += 1;
+                if i >= args.len() {
+                    eprintln!("Missing rule. Usage: {} <rule>.", side);
+                    std::process::exit(1);
+                }
+                if side == "-j" {
+                    left_rule = args[i].clone();
+                } else {
+                    right_rule = args[i].clone();
+                }
+            },
+            "--separator" => {
+                i += 1;
+                if i >= args.len() {
+                    eprintln!("Missing separator. Usage: --separator <string>.");
+                    std::process::exit(1);
+                }
+                separator = args[i].clone();
+            },
+            "--expand" => {
+                expand = true;
+            },
+            "--simplify" => {
+                simplify = true;
+            },
+            "--jtr-rules" => {
+                i += 1;
+                if i >= args.len() {
+                    eprintln!("Missing rule location. Usage: --jtr-rules <path to rule file>.");
+                    std::process::exit(1);
+                }
+
+                jtr_paths.push(args[i].clone());
+            },
+            "--export-rules" => {
+                i += 1;
+                if i >= args.len() {
+                    eprintln!("Missing export location. Usage: --export-rules <path>.");
+                    std::process::exit(1);
+                }
+
+                export_path = Some(args[i].clone());
+            },
+            "--import-rules" => {
+                i += 1;
+                if i >= args.len() {
+                    eprintln!("Missing import location. Usage: --import-rules <path>.");
+                    std::process::exit(1);
+                }
+
+                import_paths.push(args[i].clone());
+            },
+            "--jtr-section" => {
+                i += 1;
+                if i >= args.len() {
+                    eprintln!("Missing section name. Usage: --jtr-section <name>.");
+                    std::process::exit(1);
+                }
+
+                jtr_section = Some(args[i].clone());
+            },
+            "--prince" => {
+                prince = true;
+            },
+            "--elem-cnt-min" | "--elem-cnt-max" | "--pw-min" | "--pw-max" => {
+                let option = args[i].clone();
+                i += 1;
+                let n = match args.get(i).and_then(|a| a.parse().ok()) {
+                    Some(n) => n,
+                    None => {
+                        eprintln!("Missing or invalid value. Usage: {} <n>.", option);
+                        std::process::exit(1);
+                    }
+                };
+                match option.as_str() {
+                    "--elem-cnt-min" => prince_options.elem_cnt_min = n,
+                    "--elem-cnt-max" => prince_options.elem_cnt_max = n,
+                    "--pw-min" => prince_options.pw_min = n,
+                    _ => prince_options.pw_max = n,
+                }
+            },
+            "--increment" => {
+                increment = true;
+            },
+            "--increment-min" => {
+                i += 1;
+                match args.get(i).and_then(|a| a.parse().ok()) {
+                    Some(n) => increment_min = n,
+                    None => {
+                        eprintln!("Missing or invalid increment minimum. Usage: --increment-min <n>.");
+                        std::process::exit(1);
+                    }
+                }
+            },
+            "--increment-max" => {
+                i += 1;
+                match args.get(i).and_then(|a| a.parse().ok()) {
+                    Some(n) => increment_max = n,
+                    None => {
+                        eprintln!("Missing or invalid increment maximum. Usage: --increment-max <n>.");
+                        std::process::exit(1);
+                    }
+                }
+            },
             "-d" => {
                 eprintln!("Debug mode enabled.");
                 debug = true;
             },
+            positional if !positional.starts_with('-') => {
+                positionals.push(positional.to_string());
+            },
             _ => {
                 eprintln!("No such argument {}", args[i]);
                 std::process::exit(1);
@@ -50,33 +504,423 @@ fn main() {
         i += 1;
     }
 
-    if rule_paths.is_empty() {
+    if prince && attack_mode != 0 {
+        eprintln!("--prince can only be used in attack mode 0.");
+        std::process::exit(1);
+    }
+
+    // Rules are optional when a mask or word chains provide the variation
+    if rule_paths.is_empty() && jtr_paths.is_empty() && import_paths.is_empty() && generate_rules.is_none() && attack_mode == 0 && !prince {
         eprintln!("No rules specified, use -r <path to rule file>.");
 
-        eprintln!("Usage: {} [options]", args[0]);
-        eprintln!("Options:");
-        eprintln!("  -r <path to rule file>    Specify a rule file to use.");
-        eprintln!("  -h                        Display this help message.");
+        print_usage(&args[0]);
+        std::process::exit(1);
+    }
+
+    let masks = match (attack_mode, positionals.first()) {
+        (0, None) | (1, _) => None,
+        (0, Some(_)) => {
+            eprintln!("A mask requires attack mode 3, 6 or 7 (-a).");
+            std::process::exit(1);
+        },
+        (_, None) => {
+            eprintln!("No mask specified. Usage: {} -a {} [options] <mask>.", args[0], attack_mode);
+            std::process::exit(1);
+        },
+        (_, Some(m)) => match Mask::parse(m, &custom_charsets) {
+            Ok(parsed) if increment => Some(MaskList::new(parsed.increments(increment_min, increment_max))),
+            Ok(parsed) => Some(MaskList::new(vec![parsed])),
+            Err(e) => {
+                eprintln!("{}", e);
+                std::process::exit(1);
+            }
+        }
+    };
+
+    if (attack_mode == 1 && positionals.len() != 2) || (attack_mode != 1 && positionals.len() > 1) {
+        eprintln!("Too many or too few arguments, combinator mode (-a 1) takes two wordlists, the other modes at most a mask.");
+        std::process::exit(1);
+    }
+    if (attack_mode == 1 || attack_mode == 3) && wordlist_path.is_some() {
+        eprintln!("Combinator mode (-a 1) and mask mode (-a 3) do not use -w.");
         std::process::exit(1);
     }
 
+    let rule_paths_given = !rule_paths.is_empty() || !jtr_paths.is_empty() || !import_paths.is_empty();
     let mut rule_string = String::new();
-    
+    // JtR rules are compiled on their own and follow the hashcat rules
+    let mut jtr_rules: Vec<Vec<Rule>> = Vec::new();
+    // Every rule file read, for --export-rules
+    let mut sources: Vec<(String, String, Dialect)> = Vec::new();
+
     for rule_path in rule_paths {
         let contents = fs::read_to_string(&rule_path);
         match contents {
-            Ok(content) => { rule_string.push_str(&content); rule_string.push('\n'); },
+            Ok(content) if jtr::is_jtr(&content) => {
+                jtr_rules.extend(jtr::parse_rules(&content, jtr_section.as_deref(), debug));
+                sources.push((rule_path, content, Dialect::Jtr));
+            },
+            Ok(content) => {
+                rule_string.push_str(&content);
+                rule_string.push('\n');
+                sources.push((rule_path, content, Dialect::Hashcat));
+            },
             Err(_) => { eprintln!("Could not read file {}", rule_path) }
         }
     }
+    for jtr_path in jtr_paths {
+        match fs::read_to_string(&jtr_path) {
+            Ok(content) => {
+                jtr_rules.extend(jtr::parse_rules(&content, jtr_section.as_deref(), debug));
+                sources.push((jtr_path, content, Dialect::Jtr));
+            },
+            Err(_) => { eprintln!("Could not read file {}", jtr_path) }
+        }
+    }
+    let mut imported = Vec::new();
+    for import_path in import_paths {
+        match export::read(&import_path) {
+            Ok(lines) => imported.extend(lines),
+            Err(e) => {
+                eprintln!("Could not import rules from {}: {}", import_path, e);
+                std::process::exit(1);
+            }
+        }
+    }
+
+    if let Some(path) = export_path {
+        let mut lines = export::compile_sources(&sources, expand, jtr_section.as_deref(), debug);
+        lines.extend(imported);
+        if let Err(e) = export::write(&path, lines) {
+            eprintln!("Could not export rules to {}: {}", path, e);
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    if expand {
+        match template::expand(&rule_string) {
+            Ok((expanded, templates, lines)) => {
+                rule_string = expanded;
+                eprintln!("Expanded {} rule templates into {} rule lines.", templates, lines);
+            },
+            Err(e) => {
+                eprintln!("Could not expand rule templates: {}", e);
+                std::process::exit(1);
+            }
+        }
+    }
 
-    let mut rule_reader = StringReader::from_string(&rule_string);
+    let mut rules = if rule_paths_given {
+        let mut rules = parse_rule_lines(&rule_string, debug);
+        rules.extend(jtr_rules);
+        rules.extend(imported.into_iter().map(|line| line.rules));
+        rules
+    } else if generate_rules.is_none() {
+        vec![vec![Rule::Nothing]]
+    } else {
+        Vec::new()
+    };
+    // Generated from the seed, a restore point finds the same lines again
+    if let Some(n) = generate_rules {
+        if let Err(e) = generate_options.check() {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+        rules.extend(generate::generate(n, &generate_options, &mut random::Rng::new(seed)));
+    }
+    // Only the functions change, the rule lines stay the same for restore points
+    let rules: Vec<Vec<Rule>> = match simplify {
+        true => rules.iter().map(|line| simplify::simplify(line)).collect(),
+        false => rules
+    };
+
+    // In mask mode the mask replaces the wordlist, in the hybrid modes it is
+    // combined with every candidate when writing it
+    let mut hybrid = None;
+    let mut input = match (attack_mode, masks) {
+        (1, _) => {
+            let left = read_combinator_side(&positionals[0], &left_rule, debug);
+            let right = read_combinator_side(&positionals[1], &right_rule, debug);
+            Wordlist::generated(Box::new(Combinator::new(left, right, separator)))
+        },
+        (3, Some(masks)) => Wordlist::generated(Box::new(masks)),
+        (_, Some(masks)) => {
+            hybrid = Some(Hybrid { masks, append: attack_mode == 6 });
+            open_wordlist(wordlist_path.as_deref())
+        },
+        (_, None) if prince => {
+            let words = read_words(wordlist_path.as_deref());
+            Wordlist::generated(Box::new(Prince::new(words, &prince_options)))
+        },
+        (_, None) => open_wordlist(wordlist_path.as_deref())
+    };
+
+    let line_count = rules.len() as u64;
+    // In the hybrid modes every candidate is written with every mask
+    // candidate, so the keyspace and the budget count both
+    let mask_size = hybrid.as_ref().map_or(1, |h: &Hybrid| h.masks.keyspace());
+
+    if rule_major && sample_rules.is_some() {
+        eprintln!("--sample-rules cannot be combined with --rule-major.");
+        std::process::exit(1);
+    }
+    if rule_major && loopback > 0 {
+        eprintln!("--loopback cannot be combined with --rule-major.");
+        std::process::exit(1);
+    }
+
+    if keyspace {
+        match input.skip_words(u64::MAX) {
+            Ok(words) => println!("{}", words.saturating_mul(line_count).saturating_mul(mask_size)),
+            Err(e) => {
+                eprintln!("Could not read input: {}", e);
+                std::process::exit(1);
+            }
+        }
+        return;
+    }
+
+    // A word and rule line is written with all of the mask or not at all
+    if mask_size > 1 {
+        if !skip.is_multiple_of(mask_size) || limit.is_some_and(|l| !l.is_multiple_of(mask_size)) {
+            eprintln!("In the hybrid modes -s and -l must be multiples of the mask keyspace, {}.", mask_size);
+            std::process::exit(1);
+        }
+        skip /= mask_size;
+        limit = limit.map(|l| l / mask_size);
+    }
+
+    // Rule-major order reads the input once per rule line, so it has to be
+    // re-readable, and slicing the keyspace needs the number of words
+    let mut words: u64 = 0;
+    if rule_major {
+        let counted = input.buffered().and_then(|mut w| {
+            words = w.skip_words(u64::MAX)?;
+            w.seek(0, 0)?;
+            Ok(w)
+        });
+        input = match counted {
+            Ok(w) => w,
+            Err(e) => {
+                eprintln!("Could not read input: {}", e);
+                std::process::exit(1);
+            }
+        };
+    }
+
+    // A budget always leaves a restore point behind, so the run can be continued
+    if restore || max_candidates.is_some() || max_time.is_some() {
+        restore_path.get_or_insert_with(|| DEFAULT_RESTORE_PATH.to_string());
+    }
+
+    // Where to pick up the run, either the start or the restore point
+    let mut start = Restore { rules: rules.len(), rule_major, ..Restore::default() };
+    if restore {
+        let path = restore_path.as_deref().unwrap();
+        start = match Restore::load(path) {
+            Ok(r) => r,
+            Err(e) => {
+                eprintln!("{}", e);
+                std::process::exit(1);
+            }
+        };
+        if start.rules != rules.len() {
+            eprintln!("Restore file {} was written for {} rule lines, but {} were loaded.", path, start.rules, rules.len());
+            std::process::exit(1);
+        }
+        if start.rule_major != rule_major {
+            eprintln!("Restore file {} was written {} --rule-major.", path, if start.rule_major { "with" } else { "without" });
+            std::process::exit(1);
+        }
+        if let Err(e) = input.seek(start.offset, start.word) {
+            eprintln!("Could not seek to restore point: {}", e);
+            std::process::exit(1);
+        }
+        if debug {
+            eprintln!("Restoring at word {} (byte {}), rule line {}", start.word, start.offset, start.rule + 1);
+        }
+    } else if skip > 0 {
+        start = match seek_slice(&mut input, skip, rules.len(), words, rule_major) {
+            Ok(Some(start)) => start,
+            Ok(None) => {
+                eprintln!("The keyspace is empty, nothing to skip.");
+                return;
+            },
+            Err(e) => {
+                eprintln!("Could not read input: {}", e);
+                std::process::exit(1);
+            }
+        };
+    }
+
+    // With a restore file, Ctrl-C writes a last restore point before stopping,
+    // so nothing emitted since the previous one is emitted again. A second
+    // Ctrl-C ends the run right away.
+    let mut interrupted = None;
+    if restore_path.is_some() {
+        let flag = Arc::new(AtomicBool::new(false));
+        let registered = signal_hook::flag::register_conditional_shutdown(signal_hook::consts::SIGINT, 130, Arc::clone(&flag))
+            .and_then(|_| signal_hook::flag::register(signal_hook::consts::SIGINT, Arc::clone(&flag)));
+        match registered {
+            Ok(_) => interrupted = Some(flag),
+            Err(e) => eprintln!("Could not handle Ctrl-C, interrupting may emit candidates again on restore: {}", e)
+        }
+    }
+
+    let options = RunOptions {
+        restore_path,
+        restore_timer: Duration::from_secs(restore_timer),
+        end: limit.map(|l| skip.saturating_add(l)),
+        status_timer: if status { Some(Duration::from_secs(status_timer)) } else { None },
+        max_candidates,
+        max_time,
+        sample: sample_rules.map(|k| (k, seed)),
+        loopback,
+        bytes: attack_mode == 3,
+        hybrid,
+        interrupted,
+    };
+
+    let result;
+    let mut stats;
+    if rule_major {
+        // Progress covers one pass through the input per rule line
+        let size = input.size().unwrap_or(0);
+        stats = Stats::new(rules.len(), Some(size * line_count), start.rule as u64 * size + start.offset);
+        result = run::run_rule_major(&rules, &mut input, words, start, &options, &mut stats, io::stdout().lock());
+    } else {
+        stats = Stats::new(rules.len(), input.size(), start.offset);
+        result = run::run(&rules, &mut input, start, &options, &mut stats, io::stdout().lock());
+    }
+
+    if status {
+        eprintln!();
+        stats.print_summary();
+    }
+
+    match result {
+        Ok(Finished::Done) => {
+            if let Some(path) = &options.restore_path {
+                let _ = fs::remove_file(path);
+            }
+        },
+        Ok(Finished::Budget) => {
+            eprintln!("Budget reached, continue with --restore using restore file {}.", options.restore_path.as_deref().unwrap());
+        },
+        Ok(Finished::Interrupted) => {
+            eprintln!("Interrupted, continue with --restore using restore file {}.", options.restore_path.as_deref().unwrap());
+            std::process::exit(130);
+        },
+        // Output was closed, e.g. by piping into head
+        Err(_) => std::process::exit(0)
+    }
+}
+
+/// Moves `input` to the first word of the keyspace slice starting at `skip`
+/// and returns where the run starts. Words before the slice are only read,
+/// their rules are never applied. `None` if the keyspace is empty; `words` is
+/// only known, and needed, in rule-major order.
+fn seek_slice(input: &mut Wordlist, skip: u64, rules: usize, words: u64, rule_major: bool) -> io::Result<Option<Restore>> {
+    let line_count = rules as u64;
+    if line_count == 0 || (rule_major && words == 0) {
+        return Ok(None);
+    }
+    let (skip_words, skip_rules) = match rule_major {
+        false => (skip / line_count, skip % line_count),
+        true => (skip % words, skip / words)
+    };
+    input.skip_words(skip_words)?;
+    Ok(Some(Restore {
+        offset: input.offset(),
+        word: input.index(),
+        rule: skip_rules.min(line_count) as usize,
+        rules,
+        rule_major,
+        ..Restore::default()
+    }))
+}
+
+/// Opens the wordlist at `path`, or stdin
+fn open_wordlist(path: Option<&str>) -> Wordlist {
+    match path {
+        Some(path) => match Wordlist::open(path) {
+            Ok(w) => w,
+            Err(e) => {
+                eprintln!("Could not read wordlist {}: {}", path, e);
+                std::process::exit(1);
+            }
+        },
+        None => Wordlist::stdin()
+    }
+}
+
+/// Reads all words of the wordlist at `path`, or stdin, into memory
+fn read_words(path: Option<&str>) -> Vec<String> {
+    let mut input = open_wordlist(path);
+    let mut words = Vec::new();
+    loop {
+        match input.next_word() {
+            Ok(Some(word)) => words.push(word),
+            Ok(None) => break,
+            Err(e) => {
+                eprintln!("Could not read input: {}", e);
+                std::process::exit(1);
+            }
+        }
+    }
+    words
+}
+
+/// Reads one wordlist of combinator mode and applies the rule line `rule` to every word
+fn read_combinator_side(path: &str, rule: &str, debug: bool) -> Vec<String> {
+    let rule: Vec<Rule> = parse_rules(rule, debug).into_iter().flatten().collect();
+    read_words(Some(path)).iter().filter_map(|word| rule::apply_line(&rule, word)).collect()
+}
+
+/// Parses durations like `90`, `90s`, `30m`, `2h` or `1d`
+fn parse_duration(s: &str) -> Option<Duration> {
+    let (number, unit) = match s.find(|c: char| !c.is_ascii_digit()) {
+        Some(i) => s.split_at(i),
+        None => (s, "s")
+    };
+    let number: u64 = number.parse().ok()?;
+    let factor = match unit {
+        "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 24 * 60 * 60,
+        _ => return None
+    };
+    Some(Duration::from_secs(number.checked_mul(factor)?))
+}
+
+/// Compiles the content of one or more rule files into rule lines.
+fn parse_rules(rule_string: &str, debug: bool) -> Vec<Vec<Rule>> {
+    parse_rules_checked(rule_string, debug).into_iter().map(|(line, _)| line).collect()
+}
+
+/// The rule lines of hashcat rule files. Blank lines are no rule lines, as in
+/// hashcat, so they don't count in the keyspace.
+fn parse_rule_lines(rule_string: &str, debug: bool) -> Vec<Vec<Rule>> {
+    parse_rules_checked(rule_string, debug)
+        .into_iter()
+        .filter(|(line, skipped)| !line.is_empty() || !skipped.is_empty())
+        .map(|(line, _)| line)
+        .collect()
+}
+
+/// Like `parse_rules`, but also returns the functions skipped on every line,
+/// because they are unknown or not implemented.
+fn parse_rules_checked(rule_string: &str, debug: bool) -> Vec<(Vec<Rule>, Vec<char>)> {
+    let mut rule_reader = StringReader::from_string(rule_string);
 
     // All rules, line by line
-    let mut rules: Vec<Vec<Rule>> = Vec::new();
+    let mut rules: Vec<(Vec<Rule>, Vec<char>)> = Vec::new();
 
     // The current rule / current line
     let mut mangler: Vec<Rule> = Vec::new();
+    let mut skipped: Vec<char> = Vec::new();
     loop {
         // Do we have another char to read?
         match rule_reader.read() {
@@ -207,27 +1051,105 @@ fn main() {
                             },
                             _ => {let _ = rule_reader.read(); None}
                         }
-                    }
+                    },
+                    'X' => {
+                        match (rule_reader.read_usize().ok(), rule_reader.read_usize().ok(), rule_reader.read_usize().ok()) {
+                            (Some(c1), Some(c2), Some(c3)) => Some(Rule::ExtractMemory(c1, c2, c3)),
+                            _ => None
+                        }
+                    },
+                    '4' => { Some(Rule::AppendMemory()) },
+                    '6' => { Some(Rule::PrependMemory()) },
+                    'M' => { Some(Rule::Memorize()) },
+
+                    // Rules used to reject plains
+                    '<' => {
+                        match rule_reader.read_usize() {
+                            Ok(c) => Some(Rule::RejectLonger(c)),
+                            _ => None
+                        }
+                    },
+                    '>' => {
+                        match rule_reader.read_usize() {
+                            Ok(c) => Some(Rule::RejectShorter(c)),
+                            _ => None
+                        }
+                    },
+                    '_' => {
+                        match rule_reader.read_usize() {
+                            Ok(c) => Some(Rule::RejectNotLength(c)),
+                            _ => None
+                        }
+                    },
+                    '!' => {
+                        match rule_reader.read() {
+                            Ok(c) => Some(Rule::RejectContains(Class::Char(c))),
+                            _ => None
+                        }
+                    },
+                    '/' => {
+                        match rule_reader.read() {
+                            Ok(c) => Some(Rule::RejectNotContains(Class::Char(c))),
+                            _ => None
+                        }
+                    },
+                    '(' => {
+                        match rule_reader.read() {
+                            Ok(c) => Some(Rule::RejectNotFirst(Class::Char(c))),
+                            _ => None
+                        }
+                    },
+                    ')' => {
+                        match rule_reader.read() {
+                            Ok(c) => Some(Rule::RejectNotLast(Class::Char(c))),
+                            _ => None
+                        }
+                    },
+                    '=' => {
+                        match rule_reader.read_usize() {
+                            Ok(c1) => {
+                                match rule_reader.read() {
+                                    Ok(c2) => Some(Rule::RejectNotAt(c1, Class::Char(c2))),
+                                    _ => None
+                                }
+                            },
+                            _ => {let _ = rule_reader.read(); None}
+                        }
+                    },
+                    '%' => {
+                        match rule_reader.read_usize() {
+                            Ok(c1) => {
+                                match rule_reader.read() {
+                                    Ok(c2) => Some(Rule::RejectFewer(c1, Class::Char(c2))),
+                                    _ => None
+                                }
+                            },
+                            _ => {let _ = rule_reader.read(); None}
+                        }
+                    },
+                    'Q' => { Some(Rule::RejectUnchanged()) },
 
                     // These rules have not been implemented, but will be handled
                     // gracefully, so that other rules are not affected
-                    'E' => { if debug { eprintln!("{} Rule E (Title) not implemented! (handled gracefully)", rules.len() + 1); } None },
-                    'k' => { if debug { eprintln!("{} Rule k (Swap first two) not implemented! (handled gracefully)", rules.len() + 1); } None },
-                    'K' => { if debug { eprintln!("{} Rule K (Swap last two) not implemented! (handled gracefully)", rules.len() + 1); } None },
-                    'y' => { if debug { eprintln!("{} Rule y (Duplicate block front) not implemented! (handled gracefully)", rules.len() + 1); } let _ = rule_reader.read(); None },
-                    'Y' => { if debug { eprintln!("{} Rule Y (Duplicate block back) not implemented! (handled gracefully)", rules.len() + 1); } let _ = rule_reader.read(); None },
-                    '.' => { if debug { eprintln!("{} Rule . (Replace N + 1) not implemented! (handled gracefully)", rules.len() + 1); } let _ = rule_reader.read(); None },
-                    ',' => { if debug { eprintln!("{} Rule , (Replace N - 1) not implemented! (handled gracefully)", rules.len() + 1); } let _ = rule_reader.read(); None },
-                    '-' => { if debug { eprintln!("{} Rule - (Ascii Decrement) not implemented! (handled gracefully)", rules.len() + 1); } let _ = rule_reader.read(); None },
-                    '+' => { if debug { eprintln!("{} Rule + (Ascii Increment) not implemented! (handled gracefully)", rules.len() + 1); } let _ = rule_reader.read(); None },
-                    'L' => { if debug { eprintln!("{} Rule L (Bitwise shift left) not implemented! (handled gracefully)", rules.len() + 1); } let _ = rule_reader.read(); None },
-                    'R' => { if debug { eprintln!("{} Rule R (Bitwise shift right) not implemented! (handled gracefully)", rules.len() + 1); } let _ = rule_reader.read(); None },
-                    'e' => { if debug { eprintln!("{} Rule e (Title w/separator) not implemented! (handled gracefully)", rules.len() + 1); } let _ = rule_reader.read(); None },
-                    '*' => { if debug { eprintln!("{} Rule * (Swap @ N) not implemented! (handled gracefully)", rules.len() + 1); } let _ = rule_reader.read(); let _ = rule_reader.read(); None },
-                    '3' => { if debug { eprintln!("{} Rule 3 (Toggle w/Nth separator) not implemented! (handled gracefully)", rules.len() + 1); } let _ = rule_reader.read(); let _ = rule_reader.read(); None },
+                    'E' | 'k' | 'K' | 'y' | 'Y' | '.' | ',' | '-' | '+' | 'L' | 'R' | 'e' | '*' | '3' => {
+                        skipped.push(c);
+                        if debug {
+                            eprintln!("[line {}] Rule {} not implemented! (handled gracefully)", rules.len() + 1, c);
+                        }
+                        // Skip the arguments, so they are not read as rules
+                        let arguments = match c {
+                            'E' | 'k' | 'K' => 0,
+                            '*' | '3' => 2,
+                            _ => 1
+                        };
+                        for _ in 0..arguments {
+                            let _ = rule_reader.read();
+                        }
+                        None
+                    },
                     
                     // Line done
-                    '\n' => { rules.push(mangler); mangler = Vec::new(); None },
+                    '\n' => { rules.push((std::mem::take(&mut mangler), std::mem::take(&mut skipped))); None },
                     '\r' | ' ' | '\t' => { None },
                     '#' if mangler.is_empty() => { let _ = rule_reader.skip_line(); None },
                     
@@ -235,6 +1157,7 @@ fn main() {
                     // because it's potential arguments could be interpreted as
                     // other rules
                     _ => {
+                        skipped.push(c);
                         if debug {
                             eprintln!("[line {}] Unknown rule {} not implemented! This may cause other rules to fail", rules.len()+1, c);
                         }
@@ -258,185 +1181,81 @@ fn main() {
             Err(_) => {
                 // We must be at the end of the string, add the latest mangler to the list, this is
                 // causing some shit
-                rules.push(mangler);
+                rules.push((mangler, skipped));
                 break;
             }
         }
     }
 
-    // Applying compiled rules to stdin
-    // For every line in the input
-    for line in io::stdin().lock().lines() {
-        // For every rule / every line in the rule file
-        let line = line.unwrap();
-        // Required on windows cmd, not implemented
-        // let line = line.trim_end();
-
-        let mut line_out = HashSet::new();
-
-        for rule in &rules {
-            let mut out = line.clone();
-            // For every operation in the rule
-            for mangler in rule {
-                match mangler {
-                    Rule::Append(c) => { out.push(*c) }, 
-                    Rule::Prepend(c) => {
-                        let mut temp = String::new();
-                        temp.push(*c);
-                        temp.push_str(&out);
-                        out = temp;
-                    },
-                    Rule::Replace(c, o) => { out = out.replace(*c, &o.to_string().to_owned()[..]) }
-                    Rule::Lowercase() => { out = out.to_lowercase() }
-                    Rule::Uppercase() => { out = out.to_uppercase() }
-                    Rule::Capitalize() => {
-                        // Thank you stackoverflow!
-                        let mut temp: Vec<char> = out.chars().collect();
-
-                        if temp.len() > 0 {
-                            temp[0] = temp[0].to_uppercase().next().unwrap();
-                            out = temp.into_iter().collect();
-                        }
-                    },
-                    Rule::InvertCapitalize() => {
-                        if out.len() > 0 {
-                            let mut temp: Vec<char> = out.to_uppercase().chars().collect();
-                            temp[0] = temp[0].to_lowercase().next().unwrap();
-                            out = temp.into_iter().collect();
-                        }
-                    },
-                    Rule::Duplicate() => {
-                        let tmp = out.clone();
-                        out.push_str(&tmp);
-                    },
-                    Rule::Reverse() => {
-                        out = out.chars().rev().collect();
-                    },
-                    Rule::Purge(c) => {
-                        out = out.replace(*c, "");
-                    },
-                    Rule::RotateLeft() => {
-                        out = out.chars().skip(1).chain(out.chars().take(1)).collect();
-                    },
-                    Rule::RotateRight() => {
-                        if out.chars().count() > 0 {
-                            out = out.chars().rev().take(1).chain(out.chars().take(out.chars().count() - 1)).collect();
-                        }
-                    }
-                    Rule::Nothing => { }
-                    Rule::ToggleCase() => {
-                        out = out.chars().map(|c| {
-                            if c.is_lowercase() {
-                                c.to_uppercase().collect::<String>()
-                            } else {
-                                c.to_lowercase().collect::<String>()
-                            }
-                        }).collect();
-                    },
-                    Rule::ToggleAt(pos) => {
-                        // TODO: Refactor this
-                        out = out.chars().enumerate().map(|(i, c)| {
-                            if i == *pos {
-                                if c.is_lowercase() {
-                                    c.to_uppercase().collect()
-                                } else {
-                                    c.to_lowercase().collect()
-                                }
-                            } else {
-                                c.to_string()
-                            }
-                        }).collect();
-                    },
-                    Rule::DuplicateN(n) => {
-                        let mut tmp = String::new();
-                        for _ in 0..*n {
-                            tmp.push_str(&out);
-                        }
-                        out = tmp;
-                    },
-                    Rule::Reflect() => {
-                        out.push_str(&out.chars().rev().collect::<String>());
-                    },
-                    Rule::TruncateLeft() => {
-                        out = out.chars().skip(1).collect();
-                    },
-                    Rule::TruncateRight() => {
-                        if out.chars().count() > 0 {
-                            out = out.chars().take(out.chars().count() - 1).collect();
-                        }
-                    },
-                    Rule::DeleteAt(pos) => {
-                        if out.len() > *pos {
-                            out = out.chars().enumerate().filter(|(i, _)| i != pos).map(|(_, c)| c).collect();
-                        }
-                    },
-                    Rule::ExtractRange(pos, count) => {
-                        out = out.chars().enumerate().filter(|(i, _)| i >= pos && i < &(pos + count)).map(|(_, c)| c).collect();
-                    },
-                    Rule::OmitRange(pos, count) => {
-                        out = out.chars().enumerate().filter(|(i, _)| i < pos || i >= &(pos + count)).map(|(_, c)| c).collect();
-                    },
-                    Rule::InsertAt(pos, c) => {
-                        let mut tmp = String::new();
-                        for (i, ch) in out.chars().enumerate() {
-                            if i == *pos {
-                                tmp.push(*c);
-                            }
-                            tmp.push(ch);
-                        }
-                        out = tmp;
-                    },
-                    Rule::OverwriteAt(pos, c) => {
-                        let mut tmp = String::new();
-                        for (i, ch) in out.chars().enumerate() {
-                            if i == *pos {
-                                tmp.push(*c);
-                            } else {
-                                tmp.push(ch);
-                            }
-                        }
-                        out = tmp;
-                    },
-                    Rule::TruncateAt(pos) => {
-                        out = out.chars().enumerate().filter(|(i, _)| i < pos).map(|(_, c)| c).collect();
-                    },
-                    Rule::DuplicateFirstN(n) => {
-                        if &out.len() > n {
-                            let mut tmp = String::new();
-                            for _ in 0..*n {
-                                tmp.push(out.chars().next().unwrap());
-                            }
-                            tmp.push_str(&out);
-                            out = tmp;
-                        }
-                    },
-                    Rule::DuplicateLastN(n) => {
-                        if &out.len() > n {
-                            let mut tmp = String::new();
-                            tmp.push_str(&out);
-                            for _ in 0..*n {
-                                tmp.push(out.chars().last().unwrap());
-                            }
-                            out = tmp;
-                        }
-                    },
-                    Rule::DuplicateAll() => {
-                        let mut tmp = String::with_capacity(out.len() * 2);
-                        out.chars().for_each(|c| {
-                            tmp.push(c);
-                            tmp.push(c);
-                        });
-                        out = tmp;
-                    },
-                    Rule::Invalid(_) => todo!("Unknown/Invalid rule"),
-                };
+    rules
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WORDS: &str = "a\nb\nc\nd\n";
+    const RULES: &str = ":\n$1\n$2";
 
+    /// Output of the slice `skip`, `limit` of the keyspace, seeked like main does
+    fn slice(skip: u64, limit: Option<u64>, rule_major: bool) -> Vec<String> {
+        let rules = parse_rules(RULES, false);
+        let mut input = Wordlist::memory(WORDS);
+        let start = seek_slice(&mut input, skip, rules.len(), 4, rule_major).unwrap().unwrap();
+        let options = RunOptions { end: limit.map(|l| skip + l), ..RunOptions::default() };
+        let mut stats = Stats::new(rules.len(), None, 0);
+        let mut out = Vec::new();
+        match rule_major {
+            true => run::run_rule_major(&rules, &mut input, 4, start, &options, &mut stats, &mut out),
+            false => run::run(&rules, &mut input, start, &options, &mut stats, &mut out)
+        }.unwrap();
+        String::from_utf8(out).unwrap().lines().map(|l| l.to_string()).collect()
+    }
+
+    #[test]
+    fn skip_and_limit_select_a_slice_of_the_keyspace() {
+        let rules = parse_rules(RULES, false);
+        for rule_major in [false, true] {
+            // Every word and rule line gives a different candidate, so the
+            // candidates are the keyspace
+            let keyspace: Vec<String> = match rule_major {
+                false => WORDS.lines().flat_map(|w| rules.iter().map(move |r| rule::apply_line(r, w).unwrap())).collect(),
+                true => rules.iter().flat_map(|r| WORDS.lines().map(move |w| rule::apply_line(r, w).unwrap())).collect()
+            };
+            assert_eq!(keyspace.len(), 12);
+            for skip in 0..=14 {
+                let from = (skip as usize).min(keyspace.len());
+                assert_eq!(slice(skip, None, rule_major), keyspace[from..], "skip {}", skip);
+                for limit in 0..=14 {
+                    let to = (skip + limit).min(keyspace.len() as u64) as usize;
+                    assert_eq!(slice(skip, Some(limit), rule_major), keyspace[from..to], "skip {} limit {}", skip, limit);
+                }
             }
-            line_out.insert(out);
-        }
-        for o in &line_out {
-            println!("{}", o);
         }
     }
-}
 
+    #[test]
+    fn skipping_an_empty_keyspace() {
+        let mut input = Wordlist::memory(WORDS);
+        assert_eq!(seek_slice(&mut input, 5, 0, 4, false).unwrap(), None);
+        assert_eq!(seek_slice(&mut input, 5, 0, 4, true).unwrap(), None);
+        let mut input = Wordlist::memory("");
+        assert_eq!(seek_slice(&mut input, 5, 3, 0, true).unwrap(), None);
+    }
+
+    #[test]
+    fn blank_lines_and_comments_are_no_rule_lines() {
+        assert_eq!(parse_rule_lines(":\n$1\n\n", false), [vec![Rule::Nothing], vec![Rule::Append('1')]]);
+        assert_eq!(parse_rule_lines("\n# c\n  \r\n  # u\nk\n\n", false).len(), 1);
+        assert!(parse_rule_lines("", false).is_empty());
+    }
+
+    #[test]
+    fn unimplemented_rules_skip_their_arguments() {
+        let lines = parse_rules_checked("k$1\n*12u\ny2$2\n", false);
+        let skipped: Vec<&Vec<char>> = lines.iter().map(|(_, s)| s).collect();
+        assert_eq!(skipped[..3], [&vec!['k'], &vec!['*'], &vec!['y']]);
+        let words: Vec<String> = lines[..3].iter().map(|(l, _)| rule::apply_line(l, "ab").unwrap()).collect();
+        assert_eq!(words, ["ab1", "AB", "ab2"]);
+    }
+}
@@ -0,0 +1,224 @@
+// Masks as used by hashcat's mask attack
+// see https://hashcat.net/wiki/doku.php?id=mask_attack
+
+use crate::wordlist::Generator;
+
+const LOWER: &str = "abcdefghijklmnopqrstuvwxyz";
+const UPPER: &str = "ABCDEFGHIJKLMNOPQRSTUVWXYZ";
+const DIGITS: &str = "0123456789";
+const HEX_LOWER: &str = "0123456789abcdef";
+const HEX_UPPER: &str = "0123456789ABCDEF";
+const SPECIAL: &str = " !\"#$%&'()*+,-./:;<=>?@[\\]^_`{|}~";
+
+/// A mask, every position holds the bytes it iterates over. Like in
+/// hashcat, masks and charsets are bytes: a non-ASCII character is one
+/// position per byte of its UTF-8 encoding.
+#[derive(Debug, Clone)]
+pub struct Mask {
+    positions: Vec<Vec<u8>>,
+}
+
+/// Bytes of the built-in charset `?c`, `None` if there is no such charset.
+pub fn builtin(c: char) -> Option<Vec<u8>> {
+    let bytes = match c {
+        'l' => LOWER.as_bytes().to_vec(),
+        'u' => UPPER.as_bytes().to_vec(),
+        'd' => DIGITS.as_bytes().to_vec(),
+        'h' => HEX_LOWER.as_bytes().to_vec(),
+        'H' => HEX_UPPER.as_bytes().to_vec(),
+        's' => SPECIAL.as_bytes().to_vec(),
+        'a' => [LOWER, UPPER, DIGITS, SPECIAL].concat().into_bytes(),
+        'b' => (0..=255u8).collect(),
+        _ => return None
+    };
+    Some(bytes)
+}
+
+/// Expands a custom charset like `?l?d_` into its bytes, without duplicates.
+pub fn parse_charset(charset: &str) -> Result<Vec<u8>, String> {
+    let mut out: Vec<u8> = Vec::new();
+    let mut chars = charset.chars();
+    while let Some(c) = chars.next() {
+        let add = match c {
+            '?' => match chars.next() {
+                Some('?') => vec![b'?'],
+                Some(n) => builtin(n).ok_or_else(|| format!("Unknown charset ?{} in custom charset {}", n, charset))?,
+                None => return Err(format!("Custom charset {} ends with a single ?", charset))
+            },
+            _ => c.to_string().into_bytes()
+        };
+        for b in add {
+            if !out.contains(&b) {
+                out.push(b);
+            }
+        }
+    }
+    Ok(out)
+}
+
+/// Characters standing for the bytes that are no UTF-8, U+F780 to U+F7FF
+const ESCAPED: u32 = 0xF700;
+
+/// Bytes as text for the rule engine. UTF-8 sequences become their characters,
+/// so a mask literal like `É` goes through the rules like the same word of a
+/// wordlist. Other bytes, like the ones of `?b`, become U+F780 to U+F7FF,
+/// which the case functions leave alone like hashcat leaves bytes past ASCII.
+/// `to_bytes` turns such text back into the bytes.
+pub fn to_text(bytes: &[u8]) -> String {
+    let mut out = String::with_capacity(bytes.len());
+    for chunk in bytes.utf8_chunks() {
+        out.push_str(chunk.valid());
+        out.extend(chunk.invalid().iter().filter_map(|b| char::from_u32(ESCAPED + *b as u32)));
+    }
+    out
+}
+
+/// The bytes of text made by `to_text`
+pub fn to_bytes(text: &str) -> Vec<u8> {
+    let mut out = Vec::with_capacity(text.len());
+    for c in text.chars() {
+        match (c as u32).checked_sub(ESCAPED) {
+            Some(b @ 0x80..=0xFF) => out.push(b as u8),
+            _ => out.extend_from_slice(c.to_string().as_bytes())
+        }
+    }
+    out
+}
+
+impl Mask {
+    /// Parses a mask such as `?u?l?l?d?d`. `custom` holds the charsets `?1` to `?4`.
+    pub fn parse(mask: &str, custom: &[Option<Vec<u8>>; 4]) -> Result<Mask, String> {
+        let mut positions = Vec::new();
+        let mut chars = mask.chars();
+        while let Some(c) = chars.next() {
+            let position = match c {
+                '?' => match chars.next() {
+                    Some('?') => vec![b'?'],
+                    Some(n @ '1'..='4') => {
+                        let i = n.to_digit(10).unwrap() as usize - 1;
+                        custom[i].clone().ok_or_else(|| format!("Custom charset ?{} used in mask {}, but not defined with -{}", n, mask, n))?
+                    },
+                    Some(n) => builtin(n).ok_or_else(|| format!("Unknown charset ?{} in mask {}", n, mask))?,
+                    None => return Err(format!("Mask {} ends with a single ?", mask))
+                },
+                _ => {
+                    positions.extend(c.to_string().bytes().map(|b| vec![b]));
+                    continue;
+                }
+            };
+            if position.is_empty() {
+                return Err(format!("Empty charset in mask {}", mask));
+            }
+            positions.push(position);
+        }
+        Ok(Mask { positions })
+    }
+
+    /// Number of candidates of the mask
+    pub fn keyspace(&self) -> u64 {
+        self.positions.iter().fold(1u64, |k, p| k.saturating_mul(p.len() as u64))
+    }
+
+    /// The candidate at `index`, the last position changes fastest.
+    pub fn candidate(&self, mut index: u64) -> Vec<u8> {
+        let mut out = vec![0; self.positions.len()];
+        for (i, position) in self.positions.iter().enumerate().rev() {
+            let n = position.len() as u64;
+            out[i] = position[(index % n) as usize];
+            index /= n;
+        }
+        out
+    }
+
+    /// The masks of increment mode, the first `min` to `max` positions of this mask.
+    pub fn increments(&self, min: usize, max: usize) -> Vec<Mask> {
+        (min.max(1)..=max.min(self.positions.len()))
+            .map(|n| Mask { positions: self.positions[..n].to_vec() })
+            .collect()
+    }
+}
+
+/// Iterates over the candidates of one or more masks, in order.
+pub struct MaskList {
+    masks: Vec<Mask>,
+}
+
+impl MaskList {
+    pub fn new(masks: Vec<Mask>) -> MaskList {
+        MaskList { masks }
+    }
+
+    pub fn keyspace(&self) -> u64 {
+        self.masks.iter().fold(0u64, |k, m| k.saturating_add(m.keyspace()))
+    }
+
+    /// The candidate at `index` over all masks, `None` past the end.
+    pub fn candidate(&self, mut index: u64) -> Option<Vec<u8>> {
+        for mask in &self.masks {
+            let keyspace = mask.keyspace();
+            if index < keyspace {
+                return Some(mask.candidate(index));
+            }
+            index -= keyspace;
+        }
+        None
+    }
+
+    /// Calls `f` with every candidate, in order, stopping at the first error.
+    pub fn try_for_each<E>(&self, mut f: impl FnMut(&[u8]) -> Result<(), E>) -> Result<(), E> {
+        for mask in &self.masks {
+            for i in 0..mask.keyspace() {
+                f(&mask.candidate(i))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Generator for MaskList {
+    fn keyspace(&self) -> u64 {
+        MaskList::keyspace(self)
+    }
+
+    /// Bytes that are no UTF-8 go through the rules as U+F780 to U+F7FF, see `to_text`
+    fn word(&self, index: u64) -> Option<String> {
+        self.candidate(index).map(|c| to_text(&c))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn binary_charset_is_raw_bytes() {
+        let mask = Mask::parse("?b", &Default::default()).unwrap();
+        assert_eq!(mask.keyspace(), 256);
+        for i in 0..256 {
+            assert_eq!(mask.candidate(i), vec![i as u8]);
+            assert_eq!(to_bytes(&to_text(&mask.candidate(i))), vec![i as u8]);
+        }
+    }
+
+    #[test]
+    fn non_ascii_literals_are_bytes() {
+        let mask = Mask::parse("é?d", &Default::default()).unwrap();
+        assert_eq!(mask.keyspace(), 10);
+        assert_eq!(mask.candidate(3), "é3".as_bytes());
+    }
+
+    #[test]
+    fn non_ascii_literals_keep_their_case_mapping() {
+        let mask = Mask::parse("É?d", &Default::default()).unwrap();
+        let word = to_text(&mask.candidate(0));
+        assert_eq!(word, "É0");
+        let lower = crate::rule::apply_line(&[crate::rule::Rule::Lowercase()], &word).unwrap();
+        assert_eq!(to_bytes(&lower), "é0".as_bytes());
+
+        // Lone bytes stay as they are
+        let mask = Mask::parse("?b", &Default::default()).unwrap();
+        let word = to_text(&mask.candidate(0xC9));
+        let upper = crate::rule::apply_line(&[crate::rule::Rule::Uppercase()], &word).unwrap();
+        assert_eq!(to_bytes(&upper), vec![0xC9]);
+    }
+}
@@ -0,0 +1,166 @@
+// PRINCE (PRobability INfinite Chained Elements) style word chaining
+// see https://github.com/hashcat/princeprocessor
+
+use std::collections::HashSet;
+
+use crate::wordlist::Generator;
+
+/// Limits of the generated chains, named after princeprocessor's options
+pub struct PrinceOptions {
+    pub elem_cnt_min: usize,
+    pub elem_cnt_max: usize,
+    pub pw_min: usize,
+    pub pw_max: usize,
+}
+
+impl Default for PrinceOptions {
+    fn default() -> PrinceOptions {
+        PrinceOptions {
+            elem_cnt_min: 1,
+            elem_cnt_max: 8,
+            pw_min: 1,
+            pw_max: 16,
+        }
+    }
+}
+
+/// Chains of 1..N words, ordered by length, then by number of elements. A
+/// chain is the sequence of element lengths, every element iterates over all
+/// words of its length. The chains are never listed, their number grows
+/// exponentially with the length: every word is found by counting.
+pub struct Prince {
+    /// Words by length in characters
+    elements: Vec<Vec<String>>,
+    /// Words of all chains of `count` elements `len` characters long, by `[len][count]`
+    ways: Vec<Vec<u64>>,
+    /// Length and number of elements of the chains of every block
+    blocks: Vec<(usize, usize)>,
+    /// Index of the first word of every block, plus the total at the end
+    starts: Vec<u64>,
+}
+
+impl Prince {
+    pub fn new(words: Vec<String>, options: &PrinceOptions) -> Prince {
+        let mut elements: Vec<Vec<String>> = vec![Vec::new(); options.pw_max + 1];
+        let mut seen = HashSet::new();
+        for word in words {
+            let len = word.chars().count();
+            if len > 0 && len <= options.pw_max && seen.insert(word.clone()) {
+                elements[len].push(word);
+            }
+        }
+
+        let mut ways = vec![vec![0u64; options.elem_cnt_max + 1]; options.pw_max + 1];
+        ways[0][0] = 1;
+        for len in 1..=options.pw_max {
+            for count in 1..=options.elem_cnt_max {
+                ways[len][count] = (1..=len).fold(0u64, |sum, l| {
+                    sum.saturating_add((elements[l].len() as u64).saturating_mul(ways[len - l][count - 1]))
+                });
+            }
+        }
+
+        let mut blocks = Vec::new();
+        let mut starts = vec![0];
+        let mut total: u64 = 0;
+        for (len, by_count) in ways.iter().enumerate().skip(options.pw_min.max(1)) {
+            for (count, &n) in by_count.iter().enumerate().skip(options.elem_cnt_min.max(1)) {
+                if n > 0 {
+                    total = total.saturating_add(n);
+                    blocks.push((len, count));
+                    starts.push(total);
+                }
+            }
+        }
+
+        Prince { elements, ways, blocks, starts }
+    }
+}
+
+impl Generator for Prince {
+    fn keyspace(&self) -> u64 {
+        *self.starts.last().unwrap()
+    }
+
+    fn word(&self, index: u64) -> Option<String> {
+        if index >= self.keyspace() {
+            return None;
+        }
+        // The last block starting at or before index
+        let b = self.starts.partition_point(|s| *s <= index) - 1;
+        let mut rest = index - self.starts[b];
+        let (mut len, mut count) = self.blocks[b];
+
+        // Chains come in order of their element lengths, the first one
+        // changes slowest. The words of a chain before the element picked
+        // multiply the size of every chain after it.
+        let mut chain = Vec::with_capacity(count);
+        let mut words: u64 = 1;
+        while count > 0 {
+            let l = (1..=len).find(|&l| {
+                let size = words.saturating_mul(self.elements[l].len() as u64).saturating_mul(self.ways[len - l][count - 1]);
+                if rest < size {
+                    return true;
+                }
+                rest -= size;
+                false
+            })?;
+            chain.push(l);
+            words *= self.elements[l].len() as u64;
+            len -= l;
+            count -= 1;
+        }
+
+        let mut parts: Vec<&str> = vec![""; chain.len()];
+        for (i, l) in chain.iter().enumerate().rev() {
+            let words = &self.elements[*l];
+            parts[i] = &words[(rest % words.len() as u64) as usize];
+            rest /= words.len() as u64;
+        }
+        Some(parts.concat())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn all(words: &[&str], options: &PrinceOptions) -> Vec<String> {
+        let prince = Prince::new(words.iter().map(|w| w.to_string()).collect(), options);
+        (0..prince.keyspace()).map(|i| prince.word(i).unwrap()).collect()
+    }
+
+    #[test]
+    fn chains_in_order() {
+        let options = PrinceOptions { elem_cnt_min: 1, elem_cnt_max: 2, pw_min: 1, pw_max: 3 };
+        assert_eq!(all(&["a", "b", "cd", "b"], &options), [
+            "a", "b", "cd", "aa", "ab", "ba", "bb", "acd", "bcd", "cda", "cdb"
+        ]);
+        let prince = Prince::new(vec!["a".to_string()], &options);
+        assert_eq!(prince.word(prince.keyspace()), None);
+    }
+
+    #[test]
+    fn limits() {
+        let options = PrinceOptions { elem_cnt_min: 1, elem_cnt_max: 2, pw_min: 2, pw_max: 2 };
+        assert_eq!(all(&["a", "b", "cd", "efg"], &options), ["cd", "aa", "ab", "ba", "bb"]);
+        let options = PrinceOptions { elem_cnt_min: 2, elem_cnt_max: 3, pw_min: 1, pw_max: 3 };
+        assert_eq!(all(&["a", "cd"], &options), ["aa", "acd", "cda", "aaa"]);
+    }
+
+    #[test]
+    fn keyspace_of_long_chains() {
+        // Far too many chains to list them
+        let options = PrinceOptions { elem_cnt_min: 1, elem_cnt_max: 64, pw_min: 1, pw_max: 64 };
+        let prince = Prince::new(vec!["a".to_string(), "bc".to_string()], &options);
+        // Words of length n: the Fibonacci number F(n + 1)
+        let fibonacci: u64 = (1..=64).scan((1u64, 2u64), |(a, b), _| {
+            let n = *a;
+            (*a, *b) = (*b, *a + *b);
+            Some(n)
+        }).sum();
+        assert_eq!(prince.keyspace(), fibonacci);
+        assert_eq!(prince.word(prince.keyspace() - 1).unwrap(), "a".repeat(64));
+        assert_eq!(prince.word(prince.keyspace() - 2).unwrap(), format!("bc{}", "a".repeat(62)));
+    }
+}
@@ -0,0 +1,176 @@
+// Turns compiled rules back into rule syntax, in either dialect
+
+use crate::rule::{self, Class, Dialect, Pos, Rule};
+
+/// A rule line in `dialect`. Fails if one of its functions has no equivalent.
+pub fn print_line(line: &[Rule], dialect: Dialect) -> Result<String, String> {
+    let functions = line.iter()
+        .map(|r| print_rule(r, dialect))
+        .collect::<Result<Vec<String>, String>>()?;
+
+    // hashcat rule files usually separate functions, JtR rule files don't
+    let out = match dialect {
+        Dialect::Hashcat => functions.join(" "),
+        Dialect::Jtr => functions.concat()
+    };
+    if out.is_empty() {
+        return Ok(":".to_string());
+    }
+    Ok(out)
+}
+
+/// A single function in `dialect`
+pub fn print_rule(rule: &Rule, dialect: Dialect) -> Result<String, String> {
+    let out = function(rule, dialect, None)?;
+    // The JtR preprocessor sees [ and \ anywhere on the line
+    if dialect == Dialect::Jtr {
+        return Ok(out.replace('\\', "\\\\").replace('[', "\\["));
+    }
+    Ok(out)
+}
+
+fn function(rule: &Rule, dialect: Dialect, positions: Option<&[Pos]>) -> Result<String, String> {
+    let jtr = dialect == Dialect::Jtr;
+    // The i-th position of the function, variable ones take the place of the parsed value
+    let pos = |i: usize, n: usize| -> Result<char, String> {
+        match positions {
+            Some(p) => print_pos(&p[i], dialect),
+            None => print_pos(&Pos::Fixed(n), dialect)
+        }
+    };
+    let class = |c: &Class| print_class(c, dialect);
+    let unsupported = || Err(format!("{} has no equivalent in {}", name(rule), dialect_name(dialect)));
+
+    let out = match rule {
+        Rule::Nothing => ":".to_string(),
+        Rule::Lowercase() => "l".to_string(),
+        Rule::Uppercase() => "u".to_string(),
+        Rule::Capitalize() => "c".to_string(),
+        Rule::InvertCapitalize() => "C".to_string(),
+        Rule::ToggleCase() => "t".to_string(),
+        Rule::ToggleAt(n) => format!("T{}", pos(0, *n)?),
+        Rule::Reverse() => "r".to_string(),
+        Rule::Duplicate() => "d".to_string(),
+        Rule::DuplicateN(_) if jtr => return unsupported(),
+        Rule::DuplicateN(n) => format!("p{}", pos(0, *n)?),
+        Rule::Reflect() => "f".to_string(),
+        Rule::RotateLeft() => "{".to_string(),
+        Rule::RotateRight() => "}".to_string(),
+        Rule::Append(c) => format!("${}", c),
+        Rule::Prepend(c) => format!("^{}", c),
+        Rule::TruncateLeft() => "[".to_string(),
+        Rule::TruncateRight() => "]".to_string(),
+        Rule::DeleteAt(n) => format!("D{}", pos(0, *n)?),
+        Rule::ExtractRange(n, m) => format!("x{}{}", pos(0, *n)?, pos(1, *m)?),
+        Rule::OmitRange(n, m) => format!("O{}{}", pos(0, *n)?, pos(1, *m)?),
+        Rule::InsertAt(n, c) => format!("i{}{}", pos(0, *n)?, c),
+        Rule::OverwriteAt(n, c) => format!("o{}{}", pos(0, *n)?, c),
+        Rule::TruncateAt(n) => format!("'{}", pos(0, *n)?),
+        Rule::Replace(x, y) => format!("s{}{}", class(&Class::Char(*x))?, y),
+        Rule::Purge(x) => format!("@{}", class(&Class::Char(*x))?),
+        Rule::DuplicateFirstN(_) | Rule::DuplicateLastN(_) | Rule::DuplicateAll() if jtr => return unsupported(),
+        Rule::DuplicateFirstN(n) => format!("z{}", pos(0, *n)?),
+        Rule::DuplicateLastN(n) => format!("Z{}", pos(0, *n)?),
+        Rule::DuplicateAll() => "q".to_string(),
+        Rule::ExtractMemory(n, m, i) => format!("X{}{}{}", pos(0, *n)?, pos(1, *m)?, pos(2, *i)?),
+        Rule::AppendMemory() => "4".to_string(),
+        Rule::PrependMemory() => "6".to_string(),
+        Rule::Memorize() => "M".to_string(),
+
+        // JtR compares strictly, hashcat doesn't
+        Rule::RejectLonger(n) if jtr => format!("<{}", print_pos(&Pos::Fixed(n + 1), dialect)?),
+        Rule::RejectLonger(n) => format!("<{}", pos(0, *n)?),
+        Rule::RejectShorter(usize::MAX) if jtr => "<0".to_string(),
+        Rule::RejectShorter(0) if jtr => ":".to_string(),
+        Rule::RejectShorter(n) if jtr => format!(">{}", print_pos(&Pos::Fixed(n - 1), dialect)?),
+        Rule::RejectShorter(n) => format!(">{}", pos(0, *n)?),
+        Rule::RejectNotLength(n) => format!("_{}", pos(0, *n)?),
+        // hashcat has no classes, but a function for every character of the class does the same
+        Rule::RejectContains(Class::Set(c)) if !jtr => each(*c, |x| format!("!{}", x))?,
+        Rule::RejectContains(c) => format!("!{}", class(c)?),
+        Rule::RejectNotContains(c) => format!("/{}", class(c)?),
+        Rule::RejectNotFirst(c) => format!("({}", class(c)?),
+        Rule::RejectNotLast(c) => format!("){}", class(c)?),
+        Rule::RejectNotAt(n, c) => format!("={}{}", pos(0, *n)?, class(c)?),
+        Rule::RejectFewer(n, c) => format!("%{}{}", pos(0, *n)?, class(c)?),
+        Rule::RejectUnchanged() => "Q".to_string(),
+
+        Rule::ReplaceClass(Class::Set(c), y) if !jtr => each(*c, |x| format!("s{}{}", x, y))?,
+        Rule::PurgeClass(Class::Set(c)) if !jtr => each(*c, |x| format!("@{}", x))?,
+        Rule::ReplaceClass(c, y) => format!("s{}{}", class(c)?, y),
+        Rule::PurgeClass(c) => format!("@{}", class(c)?),
+        // Strings are appended or prepended a character at a time
+        Rule::InsertString(n, s) if !jtr && positions.is_none() && *n > rule::MAX_LENGTH + 1 => {
+            s.chars().map(|c| format!("${}", c)).collect::<Vec<String>>().join(" ")
+        },
+        Rule::InsertString(0, s) if !jtr && positions.is_none() => {
+            s.chars().rev().map(|c| format!("^{}", c)).collect::<Vec<String>>().join(" ")
+        },
+        Rule::InsertString(..) | Rule::SetVariable(..) | Rule::Pluralize() if !jtr => return unsupported(),
+        Rule::InsertString(n, s) => {
+            // Any character not in the string works as delimiter
+            let delimiter = ['"', '\'', '/', '|', '!', '#', '%', '~'].into_iter()
+                .find(|d| !s.contains(*d))
+                .ok_or_else(|| format!("No delimiter for the string {}", s))?;
+            format!("A{}{}{}{}", pos(0, *n)?, delimiter, s, delimiter)
+        },
+        Rule::SetVariable(v, n, m) => format!("v{}{}{}", (b'a' + *v as u8) as char, print_pos(n, dialect)?, print_pos(m, dialect)?),
+        Rule::Pluralize() => "p".to_string(),
+        Rule::Dynamic(inner, p) => return function(inner, dialect, Some(p)),
+        Rule::Invalid(e) => return Err(e.to_string())
+    };
+    Ok(out)
+}
+
+fn print_pos(pos: &Pos, dialect: Dialect) -> Result<char, String> {
+    let jtr = dialect == Dialect::Jtr;
+    let c = match pos {
+        Pos::Fixed(n @ 0..=9) => (b'0' + *n as u8) as char,
+        Pos::Fixed(n @ 10..=35) => (b'A' + (*n - 10) as u8) as char,
+        Pos::Fixed(n) if jtr && *n == rule::MAX_LENGTH => '*',
+        Pos::Fixed(n) if jtr && *n == rule::MAX_LENGTH - 1 => '-',
+        Pos::Fixed(n) if jtr && *n == rule::MAX_LENGTH + 1 => '+',
+        Pos::Fixed(n) if jtr && *n > rule::MAX_LENGTH + 1 => 'z',
+        Pos::Var(v) if jtr => (b'a' + *v as u8) as char,
+        Pos::Length if jtr => 'l',
+        Pos::Memory if jtr => 'm',
+        Pos::Found if jtr => 'p',
+        Pos::Fixed(n) => return Err(format!("Position {} cannot be written in {}", n, dialect_name(dialect))),
+        _ => return Err(format!("Variable positions have no equivalent in {}", dialect_name(dialect)))
+    };
+    Ok(c)
+}
+
+/// `f` of every character of the JtR class `set`, as hashcat functions. Only
+/// classes of a known set of ASCII characters can be written this way.
+fn each(set: char, f: impl Fn(char) -> String) -> Result<String, String> {
+    if !Class::is_set(set) || set.is_ascii_uppercase() || "oyz".contains(set) {
+        return Err(format!("Character class ?{} has no equivalent in hashcat", set));
+    }
+    let class = Class::Set(set);
+    Ok((' '..='~').filter(|c| class.matches(*c)).map(f).collect::<Vec<String>>().join(" "))
+}
+
+fn print_class(class: &Class, dialect: Dialect) -> Result<String, String> {
+    match (class, dialect) {
+        // ? starts a class in JtR
+        (Class::Char('?'), Dialect::Jtr) => Ok("??".to_string()),
+        (Class::Char(c), _) => Ok(c.to_string()),
+        (Class::Set('?'), Dialect::Hashcat) => Ok("?".to_string()),
+        (Class::Set(c), Dialect::Jtr) => Ok(format!("?{}", c)),
+        (Class::Set(c), Dialect::Hashcat) => Err(format!("Character class ?{} has no equivalent in hashcat", c))
+    }
+}
+
+fn dialect_name(dialect: Dialect) -> &'static str {
+    match dialect {
+        Dialect::Hashcat => "hashcat",
+        Dialect::Jtr => "JtR"
+    }
+}
+
+/// Short name of a function for messages
+fn name(rule: &Rule) -> String {
+    let debug = format!("{:?}", rule);
+    debug.split(['(', ' ']).next().unwrap_or_default().to_string()
+}
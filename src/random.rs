@@ -0,0 +1,40 @@
+/// Small, seedable pseudo random number generator (SplitMix64).
+/// Not suitable for anything security related, only used to make
+/// random choices reproducible for a given seed.
+pub struct Rng {
+    state: u64,
+}
+
+impl Rng {
+    pub fn new(seed: u64) -> Rng {
+        Rng { state: seed }
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    /// Random number in `0..n`, `n` must not be 0
+    pub fn below(&mut self, n: u64) -> u64 {
+        self.next_u64() % n
+    }
+
+    /// Marks `k` randomly chosen entries of `selected` as true and all others as false.
+    pub fn sample(&mut self, k: usize, selected: &mut [bool]) {
+        let n = selected.len();
+        selected.iter_mut().for_each(|s| *s = false);
+        // Floyd's algorithm, picks k distinct indices with k random numbers
+        for j in n.saturating_sub(k)..n {
+            let t = self.below(j as u64 + 1) as usize;
+            if selected[t] {
+                selected[j] = true;
+            } else {
+                selected[t] = true;
+            }
+        }
+    }
+}
@@ -0,0 +1,253 @@
+// `rank` subcommand, sorts rule lines by the cracks they produce
+//
+// Hits are counted either by applying every rule line to a base wordlist and
+// looking the candidates up in a list of cracked passwords, or from a hashcat
+// debug file written with `--debug-mode=4`, `word:rule:plain` lines. A hit is
+// unique if no other rule line produced the same cracked password.
+
+use std::collections::{HashMap, HashSet};
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, Write};
+
+use crate::source::{self, Line, Options, Shared};
+
+/// Options shared with the other subcommands
+const SHARED: &[Shared] = &[Shared::Wordlist, Shared::Report, Shared::Section];
+
+fn print_usage(program: &str) {
+    eprintln!("Usage: {} rank [options] <rule file>...", program);
+    eprintln!("Prints the rule lines sorted by the number of cracked passwords they produce.");
+    eprintln!("Options:");
+    eprintln!("  -w <path to wordlist>         Base wordlist the rules are applied to.");
+    eprintln!("  --cracked <path>              Cracked passwords, one per line.");
+    eprintln!("  --debug-file <path>           hashcat debug file of --debug-mode=4 with word:rule:plain lines,");
+    eprintln!("                                or rule:word:plain lines, instead of -w and --cracked.");
+    eprintln!("  --report <path>               Write the hits of every rule line to <path> instead of stderr.");
+    eprintln!("  --drop-unused                 Leave out the rule lines without hits.");
+    source::print_common_usage(SHARED);
+}
+
+/// Hits of a rule line
+#[derive(Default, Clone, Copy)]
+struct Hits {
+    hits: u64,
+    unique: u64,
+}
+
+pub fn main(program: &str, args: &[String]) {
+    let mut cracked_path: Option<String> = None;
+    let mut debug_path: Option<String> = None;
+    let mut drop_unused = false;
+
+    let Options { paths, mut wordlists, report: report_path, section } =
+        source::parse_args(program, args, print_usage, SHARED, |arg, args| {
+            match arg {
+                "--cracked" => cracked_path = Some(args.value("cracked passwords location", "--cracked <path>")),
+                "--debug-file" => debug_path = Some(args.value("debug file location", "--debug-file <path>")),
+                "--drop-unused" => drop_unused = true,
+                _ => return false
+            }
+            true
+        });
+    let wordlist_path = wordlists.pop();
+
+    if paths.is_empty() {
+        print_usage(program);
+        std::process::exit(1);
+    }
+
+    let lines = source::read_files(&paths, section.as_deref());
+    let header = source::header(&lines);
+    let plains = match (debug_path, wordlist_path, cracked_path) {
+        (Some(debug_path), _, _) => read_debug_file(&lines, &debug_path),
+        (None, Some(wordlist_path), Some(cracked_path)) => {
+            let words = crate::read_words(Some(&wordlist_path));
+            let cracked: HashSet<String> = crate::read_words(Some(&cracked_path)).into_iter().collect();
+            apply(&lines, &words, &cracked)
+        },
+        _ => {
+            eprintln!("Either --debug-file or -w and --cracked are required.");
+            std::process::exit(1);
+        }
+    };
+
+    let hits = count(&plains);
+    if let Some(header) = &header {
+        println!("{}", header);
+    }
+    let covered: HashSet<&String> = plains.iter().flatten().collect();
+
+    // Stable, lines with the same hits keep their order
+    let mut order: Vec<usize> = (0..lines.len()).collect();
+    order.sort_by_key(|i| std::cmp::Reverse(hits[*i].hits));
+
+    let mut report = vec!["hits\tunique\tsource\trule".to_string()];
+    for &i in &order {
+        let line = &lines[i];
+        // Lines that can't be judged are kept, like in dedupe-rules
+        if drop_unused && hits[i].hits == 0 && !line.incomplete {
+            continue;
+        }
+        println!("{}", line.text);
+        let unsupported = if line.incomplete { "\t(unsupported functions)" } else { "" };
+        report.push(format!("{}\t{}\t{}:{}\t{}{}", hits[i].hits, hits[i].unique, line.path, line.number, line.text, unsupported));
+    }
+
+    if let Err(e) = write_report(report_path.as_deref(), &report) {
+        eprintln!("Could not write report: {}", e);
+        std::process::exit(1);
+    }
+    eprintln!("Ranked {} rule lines, {} cracked passwords covered.", lines.len(), covered.len());
+}
+
+/// The cracked passwords every rule line produces from `words`. Lines with
+/// functions the engine skips can't be judged by their output and get none.
+pub fn apply(lines: &[Line], words: &[String], cracked: &HashSet<String>) -> Vec<HashSet<String>> {
+    lines.iter().map(|line| {
+        let mut found = HashSet::new();
+        if line.incomplete {
+            return found;
+        }
+        for word in words {
+            for candidate in line.apply(word) {
+                if cracked.contains(&candidate) {
+                    found.insert(candidate);
+                }
+            }
+        }
+        found
+    }).collect()
+}
+
+/// Hits and unique hits of every rule line
+fn count(plains: &[HashSet<String>]) -> Vec<Hits> {
+    let mut lines_per_plain: HashMap<&String, u64> = HashMap::new();
+    for plain in plains.iter().flatten() {
+        *lines_per_plain.entry(plain).or_default() += 1;
+    }
+    plains.iter().map(|found| Hits {
+        hits: found.len() as u64,
+        unique: found.iter().filter(|p| lines_per_plain[p] == 1).count() as u64,
+    }).collect()
+}
+
+/// The cracked passwords of every rule line according to a debug file. As
+/// rules, words and passwords may all contain `:`, every split is tried and
+/// the one where the rule turns the word into the password wins. Entries no
+/// split verifies, like the ones of lines with functions the engine skips,
+/// count for no line.
+pub fn read_debug_file(lines: &[Line], path: &str) -> Vec<HashSet<String>> {
+    let file = match File::open(path) {
+        Ok(file) => file,
+        Err(e) => {
+            eprintln!("Could not read debug file {}: {}", path, e);
+            std::process::exit(1);
+        }
+    };
+
+    let mut by_text: HashMap<&str, Vec<usize>> = HashMap::new();
+    for (i, line) in lines.iter().enumerate() {
+        by_text.entry(line.text.as_str()).or_default().push(i);
+    }
+
+    let mut plains = vec![HashSet::new(); lines.len()];
+    let mut unmatched = 0;
+    for entry in BufReader::new(file).split(b'\n') {
+        let entry = match entry {
+            Ok(entry) => String::from_utf8_lossy(&entry).trim_end_matches('\r').to_string(),
+            Err(e) => {
+                eprintln!("Could not read debug file {}: {}", path, e);
+                std::process::exit(1);
+            }
+        };
+        if entry.is_empty() {
+            continue;
+        }
+
+        match match_entry(lines, &by_text, &entry) {
+            Some((i, plain)) => { plains[i].insert(plain.to_string()); },
+            None => unmatched += 1
+        }
+    }
+    if unmatched > 0 {
+        eprintln!("{} lines of the debug file have no rule line in the rule files.", unmatched);
+    }
+    plains
+}
+
+/// The rule line and the password of a debug file entry, `word:rule:plain`
+/// as hashcat writes it with `--debug-mode=4`, or `rule:word:plain`. Words
+/// and rules may contain `:`, the split whose rule line turns the word into
+/// the plain is used.
+fn match_entry<'a>(lines: &[Line], by_text: &HashMap<&str, Vec<usize>>, entry: &'a str) -> Option<(usize, &'a str)> {
+    for (k, _) in entry.match_indices(':') {
+        let (first, rest) = (&entry[..k], &entry[k + 1..]);
+        for (j, _) in rest.match_indices(':') {
+            let (second, plain) = (&rest[..j], &rest[j + 1..]);
+            for (rule, word) in [(second, first), (first, second)] {
+                let candidates = match by_text.get(rule) {
+                    Some(candidates) => candidates,
+                    None => continue
+                };
+                for &i in candidates {
+                    if !lines[i].incomplete && lines[i].apply(word).any(|c| c == plain) {
+                        return Some((i, plain));
+                    }
+                }
+            }
+        }
+    }
+    None
+}
+
+/// Writes the report lines to `path`, or stderr
+fn write_report(path: Option<&str>, report: &[String]) -> io::Result<()> {
+    match path {
+        Some(path) => {
+            let mut file = fs::File::create(path)?;
+            report.iter().try_for_each(|r| writeln!(file, "{}", r))
+        },
+        None => {
+            report.iter().for_each(|r| eprintln!("{}", r));
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn debug_file_layouts() {
+        let lines = source::read_lines("test.rule", "$1\n:\n$:\nc\n", None);
+        let mut by_text: HashMap<&str, Vec<usize>> = HashMap::new();
+        for (i, line) in lines.iter().enumerate() {
+            by_text.entry(line.text.as_str()).or_default().push(i);
+        }
+        let entry = |e| match_entry(&lines, &by_text, e);
+
+        // hashcat --debug-mode=4
+        assert_eq!(entry("pass:$1:pass1"), Some((0, "pass1")));
+        assert_eq!(entry("pass:c:Pass"), Some((3, "Pass")));
+        assert_eq!(entry("a:b:$::a:b:"), Some((2, "a:b:")));
+        assert_eq!(entry("pass:::pass"), Some((1, "pass")));
+        // rule:word:plain
+        assert_eq!(entry("$1:pass:pass1"), Some((0, "pass1")));
+        assert_eq!(entry("c:pass:Pass"), Some((3, "Pass")));
+        assert_eq!(entry("u:pass:PASS"), None);
+        // Only verified splits count
+        assert_eq!(entry("pass:$1:pass2"), None);
+    }
+
+    #[test]
+    fn jtr_header() {
+        let lines = source::read_lines("test.rule", "[List.Rules:Digits]\n/?d\n$1\n", None);
+        assert_eq!(source::header(&lines).as_deref(), Some("[List.Rules:Digits]"));
+        assert_eq!(lines[0].apply("ab1").collect::<Vec<_>>(), vec!["ab1"]);
+        assert_eq!(lines[0].apply("abc").count(), 0);
+
+        let lines = source::read_lines("test.rule", "/?d\n", None);
+        assert_eq!(source::header(&lines), None);
+    }
+}
@@ -0,0 +1,275 @@
+// `repl` subcommand, try rule lines on a word interactively
+//
+// Rule lines are parsed by the same parsers and applied by the same functions
+// as in a run, so what the REPL shows is what a run produces. Lines starting
+// with `:` and a command name are commands, everything else is a rule line.
+
+use std::fs;
+use std::io::{self, BufRead, Write};
+
+use crate::explain;
+use crate::jtr;
+use crate::printer;
+use crate::rule::{self, Class, Dialect, Pos, Rule, State};
+use crate::source::{self, Line, Shared};
+
+const HELP: &str = "\
+<rule line>             Apply the rule line to the current word.
+:word <word>            Set the current word.
+:trace <rule line>      Apply the rule line one function at a time.
+:load <path>            Load a hashcat or JtR rule file.
+:apply                  Apply the loaded rule file to the current word.
+:dialect hashcat|jtr    Syntax of the rule lines typed (default hashcat).
+:functions              List the available functions.
+:help                   Show this help.
+:quit                   Leave, as does end of input.";
+
+/// Examples of every function, printed in the syntax of the dialect
+fn examples(dialect: Dialect) -> Vec<Rule> {
+    let mut examples = vec![
+        Rule::Nothing, Rule::Lowercase(), Rule::Uppercase(), Rule::Capitalize(), Rule::InvertCapitalize(),
+        Rule::ToggleCase(), Rule::ToggleAt(3), Rule::Reverse(), Rule::Duplicate(), Rule::Reflect(),
+        Rule::RotateLeft(), Rule::RotateRight(), Rule::Append('1'), Rule::Prepend('1'), Rule::TruncateLeft(),
+        Rule::TruncateRight(), Rule::DeleteAt(3), Rule::ExtractRange(0, 4), Rule::OmitRange(1, 2),
+        Rule::InsertAt(3, '!'), Rule::OverwriteAt(3, '!'), Rule::TruncateAt(6), Rule::Replace('a', '@'),
+        Rule::Purge('a'), Rule::ExtractMemory(0, 3, 2), Rule::AppendMemory(), Rule::PrependMemory(),
+        Rule::Memorize(), Rule::RejectLonger(8), Rule::RejectShorter(6), Rule::RejectNotLength(8),
+        Rule::RejectContains(Class::Char('a')), Rule::RejectNotContains(Class::Char('a')),
+        Rule::RejectNotFirst(Class::Char('a')), Rule::RejectNotLast(Class::Char('a')),
+        Rule::RejectNotAt(0, Class::Char('a')), Rule::RejectFewer(2, Class::Char('a')), Rule::RejectUnchanged(),
+    ];
+    match dialect {
+        Dialect::Hashcat => examples.extend([
+            Rule::DuplicateN(2), Rule::DuplicateFirstN(2), Rule::DuplicateLastN(2), Rule::DuplicateAll(),
+        ]),
+        Dialect::Jtr => examples.extend([
+            Rule::ReplaceClass(Class::Set('v'), '*'), Rule::PurgeClass(Class::Set('d')),
+            Rule::RejectContains(Class::Set('d')), Rule::InsertString(0, "123".to_string()),
+            Rule::SetVariable(0, Pos::Length, Pos::Fixed(1)), Rule::Pluralize(),
+        ]),
+    }
+    examples
+}
+
+/// Options shared with the other subcommands
+const SHARED: &[Shared] = &[Shared::Section];
+
+fn print_usage(program: &str) {
+    eprintln!("Usage: {} repl [options] [word]", program);
+    eprintln!("Reads rule lines and commands from stdin and applies the rule lines to the current word");
+    eprintln!("(default password). Type :help for the commands.");
+    eprintln!("Options:");
+    eprintln!("  -r <path to rule file>        Load a rule file.");
+    source::print_common_usage(SHARED);
+}
+
+pub fn main(program: &str, args: &[String]) {
+    let mut repl = Repl::new(io::stdout());
+    let mut rule_paths: Vec<String> = Vec::new();
+
+    let options = source::parse_args(program, args, print_usage, SHARED, |arg, args| {
+        match arg {
+            "-r" => rule_paths.push(args.value("rule file location", "-r <path to rule file>")),
+            _ => return false
+        }
+        true
+    });
+    repl.section = options.section;
+    if let Some(word) = options.paths.last() {
+        repl.word = word.clone();
+    }
+
+    for path in &rule_paths {
+        if let Err(e) = repl.load(path) {
+            exit_on_output_error(e);
+        }
+    }
+
+    let stdin = io::stdin();
+    let mut input = stdin.lock();
+    let mut entry = String::new();
+    loop {
+        print!("{}> ", repl.word);
+        let _ = io::stdout().flush();
+
+        entry.clear();
+        match input.read_line(&mut entry) {
+            Ok(0) => break,
+            Ok(_) => {},
+            Err(e) => {
+                eprintln!("Could not read input: {}", e);
+                std::process::exit(1);
+            }
+        }
+        let entry = entry.trim_end_matches(['\n', '\r']);
+        match repl.run(entry) {
+            Ok(true) => {},
+            Ok(false) => break,
+            Err(e) => exit_on_output_error(e)
+        }
+    }
+    println!();
+}
+
+/// Leaves when the answers can no longer be written, like on a closed pipe
+fn exit_on_output_error(e: io::Error) -> ! {
+    eprintln!("Could not write output: {}", e);
+    std::process::exit(1);
+}
+
+/// State of the REPL between entries
+struct Repl<W: Write> {
+    word: String,
+    dialect: Dialect,
+    lines: Vec<Line>,
+    section: Option<String>,
+    /// Where the answers go, stdout
+    out: W,
+}
+
+impl<W: Write> Repl<W> {
+    fn new(out: W) -> Repl<W> {
+        Repl { word: "password".to_string(), dialect: Dialect::Hashcat, lines: Vec::new(), section: None, out }
+    }
+
+    /// Runs a command or applies a rule line, false to leave
+    fn run(&mut self, entry: &str) -> io::Result<bool> {
+        let (command, argument) = match entry.strip_prefix(':') {
+            Some(rest) => {
+                let name_end = rest.find(|c: char| !c.is_ascii_alphabetic()).unwrap_or(rest.len());
+                let argument = rest[name_end..].strip_prefix(' ').unwrap_or(&rest[name_end..]);
+                (&rest[..name_end], argument)
+            },
+            None => ("", entry)
+        };
+
+        match command {
+            "word" => self.word = argument.to_string(),
+            "trace" => self.trace(argument)?,
+            "load" => self.load(argument.trim())?,
+            "apply" => self.apply()?,
+            "dialect" => match argument.trim() {
+                "hashcat" => self.dialect = Dialect::Hashcat,
+                "jtr" => self.dialect = Dialect::Jtr,
+                other => writeln!(self.out, "Unknown dialect {}, use hashcat or jtr.", other)?
+            },
+            "functions" => {
+                for example in examples(self.dialect) {
+                    if let Ok(printed) = printer::print_rule(&example, self.dialect) {
+                        writeln!(self.out, "{:<8}{}", printed, explain::describe_line(&[example]))?;
+                    }
+                }
+            },
+            "help" => writeln!(self.out, "{}", HELP)?,
+            "quit" => return Ok(false),
+            // Not a command, `:` is also the function doing nothing
+            _ => self.apply_entry(entry)?
+        }
+        Ok(true)
+    }
+
+    /// The rule lines of a typed line, more than one for JtR lines using the
+    /// preprocessor. None, after saying why, if it can't be compiled.
+    fn compile(&mut self, entry: &str) -> io::Result<Option<Vec<Vec<Rule>>>> {
+        if entry.trim().is_empty() {
+            return Ok(None);
+        }
+        match self.dialect {
+            Dialect::Hashcat => {
+                let (rules, skipped) = crate::parse_rules_checked(entry, false).swap_remove(0);
+                if !skipped.is_empty() {
+                    let skipped: String = skipped.into_iter().collect();
+                    writeln!(self.out, "Skipped unknown or unsupported functions: {}", skipped)?;
+                }
+                Ok(Some(vec![rules]))
+            },
+            Dialect::Jtr => match jtr::compile_line(entry) {
+                Ok(lines) => Ok(Some(lines)),
+                Err(e) => {
+                    writeln!(self.out, "{}", e)?;
+                    Ok(None)
+                }
+            }
+        }
+    }
+
+    fn apply_entry(&mut self, entry: &str) -> io::Result<()> {
+        for rules in self.compile(entry)?.unwrap_or_default() {
+            match rule::apply_line(&rules, &self.word) {
+                Some(candidate) => writeln!(self.out, "{}", candidate)?,
+                None => writeln!(self.out, "(rejected)")?
+            }
+        }
+        Ok(())
+    }
+
+    /// Applies the functions one at a time, showing the word after each
+    fn trace(&mut self, entry: &str) -> io::Result<()> {
+        for rules in self.compile(entry)?.unwrap_or_default() {
+            let mut state = State::new(&self.word);
+            writeln!(self.out, "{:<12}{}", "", state.word)?;
+            for function in &rules {
+                let printed = printer::print_rule(function, self.dialect).unwrap_or_else(|_| format!("{:?}", function));
+                if !function.apply(&mut state) {
+                    writeln!(self.out, "{:<12}{:<20}{}", printed, "(rejected)", explain::describe(function))?;
+                    break;
+                }
+                writeln!(self.out, "{:<12}{:<20}{}", printed, state.word, explain::describe(function))?;
+            }
+        }
+        Ok(())
+    }
+
+    fn load(&mut self, path: &str) -> io::Result<()> {
+        match fs::read_to_string(path) {
+            Ok(content) => {
+                self.lines = source::read_lines(path, &content, self.section.as_deref());
+                writeln!(self.out, "Loaded {} rule lines from {}.", self.lines.len(), path)
+            },
+            Err(e) => writeln!(self.out, "Could not read file {}: {}", path, e)
+        }
+    }
+
+    fn apply(&mut self) -> io::Result<()> {
+        if self.lines.is_empty() {
+            return writeln!(self.out, "No rule file loaded, use :load <path>.");
+        }
+        let width = self.lines.iter().map(|l| l.text.chars().count()).max().unwrap_or(0).min(24);
+        for line in &self.lines {
+            let candidates: Vec<String> = line.apply(&self.word).collect();
+            let shown = match () {
+                _ if line.incomplete => "(unsupported functions)".to_string(),
+                _ if candidates.is_empty() => "(rejected)".to_string(),
+                _ => candidates.join(" ")
+            };
+            writeln!(self.out, "{:<width$}  {}", line.text, shown, width = width)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// What the REPL answers to `entries`
+    fn answers(entries: &[&str]) -> String {
+        let mut repl = Repl::new(Vec::new());
+        for entry in entries {
+            assert!(repl.run(entry).unwrap());
+        }
+        String::from_utf8(repl.out).unwrap()
+    }
+
+    #[test]
+    fn commands() {
+        assert_eq!(answers(&["c $1", ":word abc", "r", "<2", ":"]), "Password1\ncba\n(rejected)\nabc\n");
+        assert_eq!(answers(&[":trace u ]"]), format!(
+            "{:<12}password\n{:<12}{:<20}uppercase all letters\n{:<12}{:<20}delete the last character\n",
+            "", "u", "PASSWORD", "]", "PASSWOR"
+        ));
+        assert_eq!(answers(&[":dialect jtr", "$[12]", ":dialect perl"]), "password1\npassword2\nUnknown dialect perl, use hashcat or jtr.\n");
+        assert_eq!(answers(&[":apply"]), "No rule file loaded, use :load <path>.\n");
+        assert!(!Repl::new(Vec::new()).run(":quit").unwrap());
+    }
+}
@@ -0,0 +1,228 @@
+// `report` subcommand, a table of what every rule line makes of sample words
+//
+// Rows are the lines of the rule files with their description, columns the
+// sample words, cells the candidates. Rejected words and candidates equal to
+// the word are highlighted, in HTML with a background colour, in Markdown
+// with a note.
+
+use std::fs;
+
+use crate::explain;
+use crate::source::{self, Line, Options, Shared};
+
+/// Sample words when none are given
+const WORDS: &[&str] = &["password", "Password1", "letmein", "abc123", "Summer2024!"];
+
+#[derive(Clone, Copy, PartialEq)]
+enum Format {
+    Markdown,
+    Html,
+}
+
+/// What a line makes of a word
+enum Cell {
+    Rejected,
+    Unchanged(String),
+    Changed(Vec<String>),
+}
+
+/// Options shared with the other subcommands
+const SHARED: &[Shared] = &[Shared::Wordlist, Shared::Section];
+
+fn print_usage(program: &str) {
+    eprintln!("Usage: {} report [options] <rule file>...", program);
+    eprintln!("Prints a Markdown or HTML table of the candidates every rule line makes of the sample words.");
+    eprintln!("Options:");
+    eprintln!("  -w <path to wordlist>         Use the words of a wordlist as sample words.");
+    eprintln!("  --word <word>                 Add a sample word, default: {}.", WORDS.join(" "));
+    eprintln!("  --format <markdown|html>      Format of the report (default markdown, html for .html output).");
+    eprintln!("  -o <path>                     Write the report to <path> instead of stdout.");
+    source::print_common_usage(SHARED);
+}
+
+pub fn main(program: &str, args: &[String]) {
+    let mut extra_words: Vec<String> = Vec::new();
+    let mut format: Option<Format> = None;
+    let mut output_path: Option<String> = None;
+
+    let Options { paths, wordlists, section, .. } =
+        source::parse_args(program, args, print_usage, SHARED, |arg, args| {
+            match arg {
+                "--word" => extra_words.push(args.value("word", "--word <word>")),
+                "--format" => {
+                    format = match args.next() {
+                        Some("markdown") | Some("md") => Some(Format::Markdown),
+                        Some("html") => Some(Format::Html),
+                        _ => {
+                            eprintln!("Invalid format. Usage: --format <markdown|html>.");
+                            std::process::exit(1);
+                        }
+                    };
+                },
+                "-o" => output_path = Some(args.value("output location", "-o <path>")),
+                _ => return false
+            }
+            true
+        });
+    let mut words: Vec<String> = wordlists.iter().flat_map(|path| crate::read_words(Some(path))).collect();
+    words.extend(extra_words);
+
+    if paths.is_empty() {
+        print_usage(program);
+        std::process::exit(1);
+    }
+    if words.is_empty() {
+        words = WORDS.iter().map(|w| w.to_string()).collect();
+    }
+    let format = format.unwrap_or(match &output_path {
+        Some(path) if path.ends_with(".html") || path.ends_with(".htm") => Format::Html,
+        _ => Format::Markdown
+    });
+
+    let lines = source::read_files(&paths, section.as_deref());
+    let report = match format {
+        Format::Markdown => markdown(&lines, &words),
+        Format::Html => html(&lines, &words),
+    };
+    match output_path {
+        Some(path) => {
+            if let Err(e) = fs::write(&path, report) {
+                eprintln!("Could not write report {}: {}", path, e);
+                std::process::exit(1);
+            }
+        },
+        None => print!("{}", report)
+    }
+}
+
+fn cell(line: &Line, word: &str) -> Cell {
+    let candidates: Vec<String> = line.apply(word).collect();
+    if candidates.is_empty() {
+        Cell::Rejected
+    } else if candidates.iter().all(|c| c == word) {
+        Cell::Unchanged(word.to_string())
+    } else {
+        Cell::Changed(candidates)
+    }
+}
+
+fn markdown(lines: &[Line], words: &[String]) -> String {
+    let mut out = String::new();
+    let header: Vec<String> = words.iter().map(|w| code(w)).collect();
+    out.push_str(&format!("| Rule | Description | {} |\n", header.join(" | ")));
+    out.push_str(&format!("|---|---|{}\n", "---|".repeat(words.len())));
+    for line in lines {
+        let cells: Vec<String> = words.iter().map(|w| match cell(line, w) {
+            _ if line.incomplete => String::new(),
+            Cell::Rejected => "*rejected*".to_string(),
+            Cell::Unchanged(word) => format!("{} *unchanged*", code(&word)),
+            Cell::Changed(candidates) => candidates.iter().map(|c| code(c)).collect::<Vec<String>>().join("<br>"),
+        }).collect();
+        out.push_str(&format!("| {} | {} | {} |\n", code(&line.text), escape_markdown(&explain::describe_source(line)), cells.join(" | ")));
+    }
+    out
+}
+
+/// A code span, longer delimiters if `s` has backticks, pipes escaped for tables
+fn code(s: &str) -> String {
+    if s.is_empty() {
+        return "*empty*".to_string();
+    }
+    let s = s.replace('|', "\\|");
+    if s.contains('`') {
+        format!("`` {} ``", s)
+    } else {
+        format!("`{}`", s)
+    }
+}
+
+fn escape_markdown(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        if "\\`*_[]<>|#".contains(c) {
+            out.push('\\');
+        }
+        out.push(c);
+    }
+    out
+}
+
+fn html(lines: &[Line], words: &[String]) -> String {
+    let mut out = String::new();
+    out.push_str("<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>Rule report</title>\n<style>\n");
+    out.push_str("table { border-collapse: collapse; font-family: sans-serif; }\n");
+    out.push_str("th, td { border: 1px solid #ccc; padding: 2px 6px; text-align: left; vertical-align: top; }\n");
+    out.push_str("code { white-space: pre; }\n");
+    out.push_str(".rejected { background: #f8d7da; }\n.unchanged { background: #fff3cd; }\n.unsupported { background: #e2e3e5; }\n");
+    out.push_str("</style>\n</head>\n<body>\n<table>\n<thead>\n<tr><th>Rule</th><th>Description</th>");
+    for word in words {
+        out.push_str(&format!("<th><code>{}</code></th>", escape_html(word)));
+    }
+    out.push_str("</tr>\n</thead>\n<tbody>\n");
+
+    for line in lines {
+        out.push_str(&format!(
+            "<tr><td><code>{}</code></td><td>{}</td>",
+            escape_html(&line.text), escape_html(&explain::describe_source(line))
+        ));
+        for word in words {
+            let td = match cell(line, word) {
+                _ if line.incomplete => "<td class=\"unsupported\"></td>".to_string(),
+                Cell::Rejected => "<td class=\"rejected\">rejected</td>".to_string(),
+                Cell::Unchanged(word) => format!("<td class=\"unchanged\"><code>{}</code></td>", escape_html(&word)),
+                Cell::Changed(candidates) => {
+                    let candidates: Vec<String> = candidates.iter().map(|c| format!("<code>{}</code>", escape_html(c))).collect();
+                    format!("<td>{}</td>", candidates.join("<br>"))
+                }
+            };
+            out.push_str(&td);
+        }
+        out.push_str("</tr>\n");
+    }
+    out.push_str("</tbody>\n</table>\n</body>\n</html>\n");
+    out
+}
+
+fn escape_html(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&#39;"),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn escaping() {
+        assert_eq!(code("a|b"), "`a\\|b`");
+        assert_eq!(code("a`b"), "`` a`b ``");
+        assert_eq!(code(""), "*empty*");
+        assert_eq!(escape_markdown("append '*' <x>"), "append '\\*' \\<x\\>");
+        assert_eq!(escape_html("<a href=\"x\">'&'</a>"), "&lt;a href=&quot;x&quot;&gt;&#39;&amp;&#39;&lt;/a&gt;");
+    }
+
+    #[test]
+    fn tables() {
+        let lines = source::read_lines("test.rule", "$|\n<2\n:\n", None);
+        let words = vec!["a<b".to_string()];
+        let markdown = markdown(&lines, &words);
+        assert_eq!(markdown.lines().nth(2).unwrap(), "| `$\\|` | Append '\\|' | `a<b\\|` |");
+        assert_eq!(markdown.lines().nth(3).unwrap(), "| `<2` | Reject words longer than 2 | *rejected* |");
+        assert_eq!(markdown.lines().nth(4).unwrap(), "| `:` | Do nothing | `a<b` *unchanged* |");
+
+        let html = html(&lines, &words);
+        assert!(html.contains("<th><code>a&lt;b</code></th>"));
+        assert!(html.contains("<tr><td><code>$|</code></td><td>Append &#39;|&#39;</td><td><code>a&lt;b|</code></td></tr>"));
+        assert!(html.contains("<td class=\"rejected\">rejected</td>"));
+    }
+}
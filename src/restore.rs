@@ -0,0 +1,53 @@
+use std::fs;
+use std::io;
+
+/// Position reached in a run: the word at `offset`/`word` in the input, and
+/// the next rule line to apply to it. `rule_major` records the iteration order
+/// of the run, as the position means something different in each.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct Restore {
+    pub offset: u64,
+    pub word: u64,
+    pub rule: usize,
+    pub rules: usize,
+    pub rule_major: bool,
+    /// Rule lines the loopback already applied to the candidates of the word,
+    /// `rule` is then past the last rule line
+    pub loopback: u64,
+}
+
+impl Restore {
+    pub fn load(path: &str) -> Result<Restore, String> {
+        let content = fs::read_to_string(path).map_err(|e| format!("Could not read restore file {}: {}", path, e))?;
+
+        let mut restore = Restore::default();
+        for line in content.lines() {
+            let (key, value) = match line.split_once('=') {
+                Some(kv) => kv,
+                None => continue
+            };
+            let invalid = |_| format!("Invalid value for {} in restore file {}", key, path);
+            match key.trim() {
+                "offset" => restore.offset = value.trim().parse().map_err(invalid)?,
+                "word" => restore.word = value.trim().parse().map_err(invalid)?,
+                "rule" => restore.rule = value.trim().parse().map_err(invalid)?,
+                "rules" => restore.rules = value.trim().parse().map_err(invalid)?,
+                "rule_major" => restore.rule_major = value.trim() == "1",
+                "loopback" => restore.loopback = value.trim().parse().map_err(invalid)?,
+                _ => {}
+            }
+        }
+        Ok(restore)
+    }
+
+    /// Writes the restore file, replacing it atomically so an interruption
+    /// never leaves a half written file behind.
+    pub fn save(&self, path: &str) -> io::Result<()> {
+        let tmp = format!("{}.tmp", path);
+        fs::write(&tmp, format!(
+            "offset={}\nword={}\nrule={}\nrules={}\nrule_major={}\nloopback={}\n",
+            self.offset, self.word, self.rule, self.rules, self.rule_major as u8, self.loopback
+        ))?;
+        fs::rename(&tmp, path)
+    }
+}
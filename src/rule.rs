@@ -2,11 +2,85 @@
 
 // Define all the rules
 // TODO:
-// "Rules used to reject plains"
 // "Implemented specific functions"
 // see https://hashcat.net/wiki/doku.php?id=rule_based_attack
+// and https://www.openwall.com/john/doc/RULES.shtml for the JtR only functions
 
-#[derive(Debug)]
+use serde::{Deserialize, Serialize};
+
+/// Maximum word length, the value of JtR's `*` position
+pub const MAX_LENGTH: usize = 125;
+
+/// Number of JtR numeric variables, `a` to `k`
+pub const VARIABLES: usize = 11;
+
+/// Syntax of a rule file
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Dialect {
+    Hashcat,
+    /// John the Ripper
+    Jtr,
+}
+
+/// Characters a function matches: a single character as in hashcat, or a
+/// JtR character class like `?v`. Upper case classes match everything else.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum Class {
+    Char(char),
+    Set(char),
+}
+
+impl Class {
+    pub fn matches(&self, c: char) -> bool {
+        match self {
+            Class::Char(x) => *x == c,
+            Class::Set(set) => {
+                let matches = match set.to_ascii_lowercase() {
+                    '?' => c == '?',
+                    'v' => "aeiouAEIOU".contains(c),
+                    'c' => c.is_ascii_alphabetic() && !"aeiouAEIOU".contains(c),
+                    'w' => c == ' ' || c == '\t',
+                    'p' => ".,:;'\"?!`".contains(c),
+                    's' => "$%^&*()-_+=|\\<>[]{}#@/~".contains(c),
+                    'l' => c.is_ascii_lowercase(),
+                    'u' => c.is_ascii_uppercase(),
+                    'd' => c.is_ascii_digit(),
+                    'a' => c.is_ascii_alphabetic(),
+                    'x' => c.is_ascii_alphanumeric(),
+                    'o' => c.is_control(),
+                    'y' => !c.is_control(),
+                    'z' => true,
+                    _ => false
+                };
+                matches != set.is_ascii_uppercase()
+            }
+        }
+    }
+
+    /// Whether `set` names a JtR character class
+    pub fn is_set(set: char) -> bool {
+        set == '?' || "vcwpsludaxoyz".contains(set.to_ascii_lowercase())
+    }
+}
+
+/// A JtR position that is only known when the rule is applied
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum Pos {
+    Fixed(usize),
+    /// Numeric variable `a` to `k`
+    Var(usize),
+    /// `l`, the length of the word when the rule started or `v` was last used
+    Length,
+    /// `m`, position of the last character of the memorized word
+    Memory,
+    /// `p`, position of the character last found with `/` or `%`
+    Found,
+}
+
+/// A rule function. Serialized as `{"function": "<variant>", "args": ...}`,
+/// the names are part of the export format and must not change.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "function", content = "args")]
 pub enum Rule{
     Nothing,
     Lowercase(),
@@ -36,12 +110,357 @@ pub enum Rule{
     DuplicateFirstN(usize),
     DuplicateLastN(usize),
     DuplicateAll(),
-    // ExtractMemory(usize, usize, usize),
-    // AppendMemory(),
-    // PrependMemory(),
-    // Memorize()
+    ExtractMemory(usize, usize, usize),
+    AppendMemory(),
+    PrependMemory(),
+    Memorize(),
+
+    // Rejection, a rejected word produces no candidate
+    RejectLonger(usize),
+    RejectShorter(usize),
+    RejectNotLength(usize),
+    RejectContains(Class),
+    RejectNotContains(Class),
+    RejectNotFirst(Class),
+    RejectNotLast(Class),
+    RejectNotAt(usize, Class),
+    RejectFewer(usize, Class),
+    RejectUnchanged(),
+
+    // JtR only
+    ReplaceClass(Class, char),
+    PurgeClass(Class),
+    InsertString(usize, String),
+    SetVariable(usize, Pos, Pos),
+    Pluralize(),
+    /// A function whose positions are resolved when applied, see `with_positions`
+    Dynamic(Box<Rule>, Vec<Pos>),
 
-    Invalid(&'static str)
+    #[allow(dead_code)]
+    #[serde(skip)]
+    Invalid(String)
 }
 
+/// A word while a rule line is applied to it, with the memory and
+/// variables some functions need.
+pub struct State {
+    pub word: String,
+    memory: String,
+    vars: [usize; VARIABLES],
+    length: usize,
+    found: usize,
+}
+
+impl State {
+    pub fn new(word: &str) -> State {
+        State {
+            word: word.to_string(),
+            memory: word.to_string(),
+            vars: [0; VARIABLES],
+            length: word.chars().count(),
+            found: 0,
+        }
+    }
+
+    fn resolve(&self, pos: &Pos) -> usize {
+        match pos {
+            Pos::Fixed(n) => *n,
+            Pos::Var(v) => self.vars[*v],
+            Pos::Length => self.length,
+            Pos::Memory => self.memory.chars().count().saturating_sub(1),
+            Pos::Found => self.found,
+        }
+    }
+}
+
+impl Rule {
+    /// Applies a single rule function to the word in `state`.
+    /// Returns false if the word is rejected.
+    pub fn apply(&self, state: &mut State) -> bool {
+        let len = state.word.chars().count();
+        match self {
+            Rule::RejectLonger(n) => return len <= *n,
+            Rule::RejectShorter(n) => return len >= *n,
+            Rule::RejectNotLength(n) => return len == *n,
+            Rule::RejectContains(class) => return !state.word.chars().any(|c| class.matches(c)),
+            Rule::RejectNotContains(class) => {
+                match state.word.chars().position(|c| class.matches(c)) {
+                    Some(i) => state.found = i,
+                    None => return false
+                }
+            },
+            Rule::RejectNotFirst(class) => return state.word.chars().next().is_some_and(|c| class.matches(c)),
+            Rule::RejectNotLast(class) => return state.word.chars().last().is_some_and(|c| class.matches(c)),
+            Rule::RejectNotAt(pos, class) => return state.word.chars().nth(*pos).is_some_and(|c| class.matches(c)),
+            Rule::RejectFewer(n, class) => {
+                let mut count = 0;
+                for (i, c) in state.word.chars().enumerate() {
+                    if class.matches(c) {
+                        count += 1;
+                        if count == *n {
+                            state.found = i;
+                        }
+                    }
+                }
+                return count >= *n;
+            },
+            Rule::RejectUnchanged() => return state.word != state.memory,
+            Rule::Memorize() => state.memory = state.word.clone(),
+            Rule::AppendMemory() => state.word.push_str(&state.memory),
+            Rule::PrependMemory() => state.word.insert_str(0, &state.memory),
+            Rule::ExtractMemory(pos, count, at) => {
+                let part: String = state.memory.chars().skip(*pos).take(*count).collect();
+                if *at <= len {
+                    let i = state.word.char_indices().nth(*at).map_or(state.word.len(), |(i, _)| i);
+                    state.word.insert_str(i, &part);
+                }
+            },
+            Rule::SetVariable(var, n, m) => {
+                state.length = len;
+                state.vars[*var] = state.resolve(n).saturating_sub(state.resolve(m));
+            },
+            Rule::Dynamic(rule, positions) => {
+                let positions: Vec<usize> = positions.iter().map(|p| state.resolve(p)).collect();
+                return rule.with_positions(&positions).apply(state);
+            },
+            _ => state.word = self.mangle(std::mem::take(&mut state.word))
+        }
+        true
+    }
+
+    /// Copy of a positional function with its positions replaced, in the
+    /// order they are written in the rule. Used for JtR's variable positions.
+    pub fn with_positions(&self, p: &[usize]) -> Rule {
+        match self {
+            Rule::ToggleAt(_) => Rule::ToggleAt(p[0]),
+            Rule::DeleteAt(_) => Rule::DeleteAt(p[0]),
+            Rule::ExtractRange(_, _) => Rule::ExtractRange(p[0], p[1]),
+            Rule::OmitRange(_, _) => Rule::OmitRange(p[0], p[1]),
+            Rule::InsertAt(_, c) => Rule::InsertAt(p[0], *c),
+            Rule::OverwriteAt(_, c) => Rule::OverwriteAt(p[0], *c),
+            Rule::TruncateAt(_) => Rule::TruncateAt(p[0]),
+            Rule::ExtractMemory(_, _, _) => Rule::ExtractMemory(p[0], p[1], p[2]),
+            Rule::RejectNotAt(_, class) => Rule::RejectNotAt(p[0], *class),
+            Rule::RejectFewer(_, class) => Rule::RejectFewer(p[0], *class),
+            Rule::InsertString(_, s) => Rule::InsertString(p[0], s.clone()),
+            _ => unreachable!("{:?} has no positions", self)
+        }
+    }
+
+    /// Number of positions `with_positions` replaces, `None` for functions
+    /// without positions.
+    pub fn position_count(&self) -> Option<usize> {
+        match self {
+            Rule::ToggleAt(_) | Rule::DeleteAt(_) | Rule::InsertAt(_, _) | Rule::OverwriteAt(_, _)
+                | Rule::TruncateAt(_) | Rule::RejectNotAt(_, _) | Rule::RejectFewer(_, _)
+                | Rule::InsertString(_, _) => Some(1),
+            Rule::ExtractRange(_, _) | Rule::OmitRange(_, _) => Some(2),
+            Rule::ExtractMemory(_, _, _) => Some(3),
+            _ => None
+        }
+    }
 
+    /// Applies a function that only depends on the word itself.
+    fn mangle(&self, mut out: String) -> String {
+        match self {
+            Rule::Append(c) => { out.push(*c) },
+            Rule::Prepend(c) => {
+                let mut temp = String::new();
+                temp.push(*c);
+                temp.push_str(&out);
+                out = temp;
+            },
+            Rule::Replace(c, o) => { out = out.replace(*c, &o.to_string()) }
+            Rule::Lowercase() => { out = out.to_lowercase() }
+            Rule::Uppercase() => { out = out.to_uppercase() }
+            Rule::Capitalize() => {
+                // Thank you stackoverflow!
+                let mut temp: Vec<char> = out.chars().collect();
+
+                if !temp.is_empty() {
+                    temp[0] = temp[0].to_uppercase().next().unwrap();
+                    out = temp.into_iter().collect();
+                }
+            },
+            Rule::InvertCapitalize() => {
+                if !out.is_empty() {
+                    let mut temp: Vec<char> = out.to_uppercase().chars().collect();
+                    temp[0] = temp[0].to_lowercase().next().unwrap();
+                    out = temp.into_iter().collect();
+                }
+            },
+            Rule::Duplicate() => {
+                let tmp = out.clone();
+                out.push_str(&tmp);
+            },
+            Rule::Reverse() => {
+                out = out.chars().rev().collect();
+            },
+            Rule::Purge(c) => {
+                out = out.replace(*c, "");
+            },
+            Rule::RotateLeft() => {
+                out = out.chars().skip(1).chain(out.chars().take(1)).collect();
+            },
+            Rule::RotateRight() => {
+                if out.chars().count() > 0 {
+                    out = out.chars().rev().take(1).chain(out.chars().take(out.chars().count() - 1)).collect();
+                }
+            }
+            Rule::Nothing => { }
+            Rule::ToggleCase() => {
+                out = out.chars().map(|c| {
+                    if c.is_lowercase() {
+                        c.to_uppercase().collect::<String>()
+                    } else {
+                        c.to_lowercase().collect::<String>()
+                    }
+                }).collect();
+            },
+            Rule::ToggleAt(pos) => {
+                // TODO: Refactor this
+                out = out.chars().enumerate().map(|(i, c)| {
+                    if i == *pos {
+                        if c.is_lowercase() {
+                            c.to_uppercase().collect()
+                        } else {
+                            c.to_lowercase().collect()
+                        }
+                    } else {
+                        c.to_string()
+                    }
+                }).collect();
+            },
+            Rule::DuplicateN(n) => {
+                let mut tmp = String::new();
+                for _ in 0..*n {
+                    tmp.push_str(&out);
+                }
+                out = tmp;
+            },
+            Rule::Reflect() => {
+                out.push_str(&out.chars().rev().collect::<String>());
+            },
+            Rule::TruncateLeft() => {
+                out = out.chars().skip(1).collect();
+            },
+            Rule::TruncateRight() => {
+                if out.chars().count() > 0 {
+                    out = out.chars().take(out.chars().count() - 1).collect();
+                }
+            },
+            Rule::DeleteAt(pos) => {
+                if out.len() > *pos {
+                    out = out.chars().enumerate().filter(|(i, _)| i != pos).map(|(_, c)| c).collect();
+                }
+            },
+            Rule::ExtractRange(pos, count) => {
+                out = out.chars().enumerate().filter(|(i, _)| i >= pos && *i < pos.saturating_add(*count)).map(|(_, c)| c).collect();
+            },
+            Rule::OmitRange(pos, count) => {
+                out = out.chars().enumerate().filter(|(i, _)| i < pos || *i >= pos.saturating_add(*count)).map(|(_, c)| c).collect();
+            },
+            Rule::InsertAt(pos, c) => {
+                let mut tmp = String::new();
+                for (i, ch) in out.chars().enumerate() {
+                    if i == *pos {
+                        tmp.push(*c);
+                    }
+                    tmp.push(ch);
+                }
+                out = tmp;
+            },
+            Rule::OverwriteAt(pos, c) => {
+                let mut tmp = String::new();
+                for (i, ch) in out.chars().enumerate() {
+                    if i == *pos {
+                        tmp.push(*c);
+                    } else {
+                        tmp.push(ch);
+                    }
+                }
+                out = tmp;
+            },
+            Rule::TruncateAt(pos) => {
+                out = out.chars().enumerate().filter(|(i, _)| i < pos).map(|(_, c)| c).collect();
+            },
+            Rule::DuplicateFirstN(n) => {
+                if &out.len() > n {
+                    let mut tmp = String::new();
+                    for _ in 0..*n {
+                        tmp.push(out.chars().next().unwrap());
+                    }
+                    tmp.push_str(&out);
+                    out = tmp;
+                }
+            },
+            Rule::DuplicateLastN(n) => {
+                if &out.len() > n {
+                    let mut tmp = String::new();
+                    tmp.push_str(&out);
+                    for _ in 0..*n {
+                        tmp.push(out.chars().last().unwrap());
+                    }
+                    out = tmp;
+                }
+            },
+            Rule::DuplicateAll() => {
+                let mut tmp = String::with_capacity(out.len() * 2);
+                out.chars().for_each(|c| {
+                    tmp.push(c);
+                    tmp.push(c);
+                });
+                out = tmp;
+            },
+            Rule::ReplaceClass(class, o) => {
+                out = out.chars().map(|c| if class.matches(c) { *o } else { c }).collect();
+            },
+            Rule::PurgeClass(class) => {
+                out = out.chars().filter(|c| !class.matches(*c)).collect();
+            },
+            // Positions past the end append, `Az"..."` is JtR's way to append a string
+            Rule::InsertString(pos, s) => {
+                let i = out.char_indices().nth(*pos).map_or(out.len(), |(i, _)| i);
+                out.insert_str(i, s);
+            },
+            Rule::Pluralize() => {
+                out = pluralize(&out);
+            },
+            Rule::Invalid(_) => todo!("Unknown/Invalid rule"),
+            _ => unreachable!("{:?} needs the rule state", self)
+        };
+        out
+    }
+}
+
+/// JtR's `p`, the plural of an (English) word
+fn pluralize(word: &str) -> String {
+    let lower = word.to_lowercase();
+    let chars: Vec<char> = word.chars().collect();
+    let before_last = lower.chars().rev().nth(1);
+    let stem = |n: usize| chars[..chars.len() - n].iter().collect::<String>();
+
+    if ["s", "x", "z", "ch", "sh"].iter().any(|e| lower.ends_with(e)) {
+        format!("{}es", word)
+    } else if lower.ends_with('y') && before_last.is_some_and(|c| !"aeiou".contains(c)) {
+        format!("{}ies", stem(1))
+    } else if lower.ends_with("fe") {
+        format!("{}ves", stem(2))
+    } else if lower.ends_with('f') && before_last != Some('f') {
+        format!("{}ves", stem(1))
+    } else {
+        format!("{}s", word)
+    }
+}
+
+/// Applies every function of a rule line to `word`, in order.
+/// Returns `None` if the word was rejected.
+pub fn apply_line(line: &[Rule], word: &str) -> Option<String> {
+    let mut state = State::new(word);
+    for mangler in line {
+        if !mangler.apply(&mut state) {
+            return None;
+        }
+    }
+    Some(state.word)
+}
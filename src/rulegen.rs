@@ -0,0 +1,444 @@
+// `rulegen` subcommand, infers rule lines from word/password pairs
+//
+// Like PACK's rulegen: whole-word functions (reverse, duplicate, case and
+// leet substitutions) are tried first, then every shortest Levenshtein edit
+// script from the result to the password is turned into functions. Inserts
+// and deletes at the ends become `^`, `$`, `[` and `]`, the others `i`, `D`
+// and `o`. Every line is checked by applying it to the word.
+
+use std::collections::HashMap;
+use std::fs;
+use std::io::{self, Write};
+
+use crate::printer;
+use crate::rule::{self, Dialect, Rule};
+use crate::source::{self, Options, Shared};
+
+/// Edit scripts tried per prelude, ties beyond it are dropped
+const MAX_SCRIPTS: usize = 32;
+/// Base words tried per password
+const MAX_BASE_WORDS: usize = 8;
+/// Leet substitutions tried in every combination, more are tried all or none
+const MAX_SUBSTITUTIONS: usize = 4;
+
+/// Common substitutions of a lowercase letter
+const LEET: &[(char, char)] = &[
+    ('a', '4'), ('a', '@'), ('b', '8'), ('e', '3'), ('g', '9'), ('i', '1'), ('i', '!'),
+    ('l', '1'), ('o', '0'), ('s', '5'), ('s', '$'), ('t', '7'),
+];
+
+#[derive(Clone, Copy)]
+enum Edit {
+    Keep,
+    Insert(char),
+    Delete,
+    Replace(char),
+}
+
+/// Options shared with the other subcommands
+const SHARED: &[Shared] = &[Shared::Wordlist, Shared::Report];
+
+fn print_usage(program: &str) {
+    eprintln!("Usage: {} rulegen [options] <word> <password>", program);
+    eprintln!("       {} rulegen [options] -w <path to wordlist> --cracked <path>", program);
+    eprintln!("Prints the shortest rule lines turning the word into the password, or the rule lines");
+    eprintln!("found for the cracked passwords sorted by the number of passwords they produce.");
+    eprintln!("Options:");
+    eprintln!("  -w <path to wordlist>         Base words the cracked passwords are matched against.");
+    eprintln!("  --cracked <path>              Cracked passwords, one per line.");
+    eprintln!("  --max-functions <n>           Skip passwords needing more than <n> functions (default 10).");
+    eprintln!("  --report <path>               Write the count of every rule line to <path> instead of stderr.");
+    source::print_common_usage(SHARED);
+}
+
+pub fn main(program: &str, args: &[String]) {
+    let mut cracked_path: Option<String> = None;
+    let mut max_functions: usize = 10;
+
+    let Options { paths: pair, mut wordlists, report: report_path, .. } =
+        source::parse_args(program, args, print_usage, SHARED, |arg, args| {
+            match arg {
+                "--cracked" => cracked_path = Some(args.value("cracked passwords location", "--cracked <path>")),
+                "--max-functions" => {
+                    match args.next().and_then(|n| n.parse::<usize>().ok()) {
+                        Some(n) if n > 0 => max_functions = n,
+                        _ => {
+                            eprintln!("Invalid number of functions. Usage: --max-functions <n>.");
+                            std::process::exit(1);
+                        }
+                    }
+                },
+                _ => return false
+            }
+            true
+        });
+    let wordlist_path = wordlists.pop();
+
+    match (pair.as_slice(), wordlist_path, cracked_path) {
+        ([word, password], None, None) => {
+            let lines = generate(word, password);
+            if lines.is_empty() || lines[0].len() > max_functions {
+                eprintln!("No rule line of at most {} functions turns {} into {}.", max_functions, word, password);
+                std::process::exit(1);
+            }
+            lines.iter().filter_map(|l| printer::print_line(l, Dialect::Hashcat).ok()).for_each(|l| println!("{}", l));
+        },
+        ([], Some(wordlist_path), Some(cracked_path)) => {
+            let words = crate::read_words(Some(&wordlist_path));
+            let passwords = crate::read_words(Some(&cracked_path));
+            corpus(&words, &passwords, max_functions, report_path.as_deref());
+        },
+        _ => {
+            eprintln!("Either a word and a password or -w and --cracked are required.");
+            print_usage(program);
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Counts the rule lines of every cracked password and prints them, most frequent first
+fn corpus(words: &[String], passwords: &[String], max_functions: usize, report_path: Option<&str>) {
+    let (counts, unmatched) = count_lines(words, passwords, max_functions);
+    let mut report = vec!["count\trule".to_string()];
+    for (line, count) in &counts {
+        println!("{}", line);
+        report.push(format!("{}\t{}", count, line));
+    }
+
+    let written = match report_path {
+        Some(path) => fs::File::create(path).and_then(|mut file| report.iter().try_for_each(|r| writeln!(file, "{}", r))),
+        None => {
+            report.iter().for_each(|r| eprintln!("{}", r));
+            Ok::<(), io::Error>(())
+        }
+    };
+    if let Err(e) = written {
+        eprintln!("Could not write report: {}", e);
+        std::process::exit(1);
+    }
+    eprintln!(
+        "Found {} rule lines for {} of {} passwords ({} without a base word or with longer rule lines).",
+        counts.len(), passwords.len() - unmatched, passwords.len(), unmatched
+    );
+}
+
+/// The rule lines of the cracked passwords with their counts, most frequent
+/// first, and the number of passwords without any
+fn count_lines(words: &[String], passwords: &[String], max_functions: usize) -> (Vec<(String, u64)>, usize) {
+    let mut by_lowercase: HashMap<String, Vec<&str>> = HashMap::new();
+    for word in words {
+        let entry = by_lowercase.entry(word.to_lowercase()).or_default();
+        if !entry.contains(&word.as_str()) {
+            entry.push(word);
+        }
+    }
+
+    // Count of every printed rule line, and the order they were first found in
+    let mut counts: HashMap<String, u64> = HashMap::new();
+    let mut order: Vec<String> = Vec::new();
+    let mut unmatched = 0;
+    for password in passwords {
+        let mut best: Vec<Vec<Rule>> = Vec::new();
+        for word in base_words(&by_lowercase, password) {
+            let lines = generate(word, password);
+            match (lines.first(), best.first()) {
+                (Some(l), Some(b)) if cost(l) < cost(b) => best = lines,
+                (Some(l), Some(b)) if cost(l) == cost(b) => best.extend(lines),
+                (Some(_), None) => best = lines,
+                _ => {}
+            }
+        }
+        if best.is_empty() || best[0].len() > max_functions {
+            unmatched += 1;
+            continue;
+        }
+
+        let mut printed: Vec<String> = Vec::new();
+        for line in best.iter().filter_map(|l| printer::print_line(l, Dialect::Hashcat).ok()) {
+            if !printed.contains(&line) {
+                printed.push(line);
+            }
+        }
+        for line in printed {
+            let count = counts.entry(line.clone()).or_default();
+            if *count == 0 {
+                order.push(line);
+            }
+            *count += 1;
+        }
+    }
+
+    // Stable, lines with the same count keep the order they were found in
+    order.sort_by_key(|l| std::cmp::Reverse(counts[l]));
+    (order.into_iter().map(|l| {
+        let count = counts[&l];
+        (l, count)
+    }).collect(), unmatched)
+}
+
+/// The base words sharing the longest run of letters with `password`, leet
+/// substitutions undone and case ignored
+fn base_words<'a>(by_lowercase: &HashMap<String, Vec<&'a str>>, password: &str) -> Vec<&'a str> {
+    let lowercase: Vec<char> = password.to_lowercase().chars().collect();
+    // `1` stands for either `i` or `l`
+    let variants: Vec<Vec<char>> = ['i', 'l'].iter().map(|one| lowercase.iter().map(|&c| match c {
+        '1' => *one,
+        c => LEET.iter().find(|(_, y)| *y == c && c != '1').map_or(c, |(x, _)| *x)
+    }).collect()).collect();
+
+    let mut found: Vec<&str> = Vec::new();
+    for length in (1..=lowercase.len()).rev() {
+        for variant in [&lowercase].into_iter().chain(&variants) {
+            for start in 0..=variant.len() - length {
+                let part: String = variant[start..start + length].iter().collect();
+                for word in by_lowercase.get(&part).into_iter().flatten() {
+                    if !found.contains(word) && found.len() < MAX_BASE_WORDS {
+                        found.push(word);
+                    }
+                }
+            }
+        }
+        if !found.is_empty() {
+            break;
+        }
+    }
+    found
+}
+
+/// Number of functions, then number of positional functions. Lines working
+/// on the ends of the word are preferred as they apply to more words.
+fn cost(line: &[Rule]) -> (usize, usize) {
+    let positional = line.iter().filter(|r| matches!(r, Rule::InsertAt(_, _) | Rule::DeleteAt(_) | Rule::OverwriteAt(_, _))).count();
+    (line.len(), positional)
+}
+
+/// The cheapest rule lines turning `word` into `password`, empty if none
+/// can be printed as a hashcat rule
+pub fn generate(word: &str, password: &str) -> Vec<Vec<Rule>> {
+    let target: Vec<char> = password.chars().collect();
+    let mut best: Vec<Vec<Rule>> = Vec::new();
+    for prelude in preludes(word, password) {
+        let source: Vec<char> = match rule::apply_line(&prelude, word) {
+            Some(start) => start.chars().collect(),
+            None => continue
+        };
+        let table = distances(&source, &target);
+        // Every edit is one function, a prelude this long can't win
+        if best.first().is_some_and(|b| prelude.len() + table[source.len()][target.len()] > b.len()) {
+            continue;
+        }
+
+        let mut scripts = Vec::new();
+        backtrack(&table, &source, &target, source.len(), target.len(), &mut Vec::new(), &mut scripts);
+        for script in scripts {
+            let mut line = prelude.clone();
+            line.extend(to_rules(&script));
+            if best.first().is_some_and(|b| cost(&line) > cost(b)) || best.contains(&line) {
+                continue;
+            }
+            if rule::apply_line(&line, word).as_deref() != Some(password) || printer::print_line(&line, Dialect::Hashcat).is_err() {
+                continue;
+            }
+            if best.first().is_some_and(|b| cost(&line) < cost(b)) {
+                best.clear();
+            }
+            best.push(line);
+        }
+    }
+    best
+}
+
+/// Whole-word functions worth trying before the edit script
+fn preludes(word: &str, password: &str) -> Vec<Vec<Rule>> {
+    let mut structures = vec![vec![], vec![Rule::Reverse()]];
+    if password.chars().count() >= 2 * word.chars().count() && !word.is_empty() {
+        structures.push(vec![Rule::Duplicate()]);
+    }
+    let cases = [None, Some(Rule::Lowercase()), Some(Rule::Uppercase()), Some(Rule::Capitalize()), Some(Rule::InvertCapitalize()), Some(Rule::ToggleCase())];
+
+    let mut out = Vec::new();
+    for structure in &structures {
+        for case in &cases {
+            let mut base = structure.clone();
+            base.extend(case.clone());
+            let start = match rule::apply_line(&base, word) {
+                Some(start) => start,
+                None => continue
+            };
+
+            // Substitutions of letters the word has and the password has not
+            let mut substitutions: Vec<Rule> = Vec::new();
+            for x in start.chars() {
+                for (_, y) in LEET.iter().filter(|(l, _)| x.to_ascii_lowercase() == *l) {
+                    let substitution = Rule::Replace(x, *y);
+                    if password.contains(*y) && !password.contains(x) && !substitutions.contains(&substitution) {
+                        substitutions.push(substitution);
+                    }
+                }
+            }
+
+            if substitutions.len() > MAX_SUBSTITUTIONS {
+                out.push(base.clone());
+                out.push(base.iter().chain(&substitutions).cloned().collect());
+                continue;
+            }
+            for mask in 0..1usize << substitutions.len() {
+                let picked = substitutions.iter().enumerate().filter(|(i, _)| mask & (1 << i) != 0).map(|(_, s)| s.clone());
+                out.push(base.iter().cloned().chain(picked).collect());
+            }
+        }
+    }
+    out
+}
+
+/// Levenshtein distances between every prefix of `source` and `target`
+fn distances(source: &[char], target: &[char]) -> Vec<Vec<usize>> {
+    let mut table = vec![vec![0; target.len() + 1]; source.len() + 1];
+    for (i, row) in table.iter_mut().enumerate() {
+        row[0] = i;
+    }
+    for (j, cell) in table[0].iter_mut().enumerate() {
+        *cell = j;
+    }
+    for i in 1..=source.len() {
+        for j in 1..=target.len() {
+            let replace = table[i - 1][j - 1] + usize::from(source[i - 1] != target[j - 1]);
+            table[i][j] = replace.min(table[i - 1][j] + 1).min(table[i][j - 1] + 1);
+        }
+    }
+    table
+}
+
+/// Collects up to `MAX_SCRIPTS` shortest edit scripts, walking the table back from `(i, j)`
+fn backtrack(table: &[Vec<usize>], source: &[char], target: &[char], i: usize, j: usize, path: &mut Vec<Edit>, out: &mut Vec<Vec<Edit>>) {
+    if out.len() >= MAX_SCRIPTS {
+        return;
+    }
+    if i == 0 && j == 0 {
+        out.push(path.iter().rev().cloned().collect());
+        return;
+    }
+
+    let d = table[i][j];
+    let mut step = |edit: Edit, i: usize, j: usize, out: &mut Vec<Vec<Edit>>| {
+        path.push(edit);
+        backtrack(table, source, target, i, j, path, out);
+        path.pop();
+    };
+    if i > 0 && j > 0 && source[i - 1] == target[j - 1] && table[i - 1][j - 1] == d {
+        step(Edit::Keep, i - 1, j - 1, out);
+    }
+    if i > 0 && j > 0 && source[i - 1] != target[j - 1] && table[i - 1][j - 1] + 1 == d {
+        step(Edit::Replace(target[j - 1]), i - 1, j - 1, out);
+    }
+    if j > 0 && table[i][j - 1] + 1 == d {
+        step(Edit::Insert(target[j - 1]), i, j - 1, out);
+    }
+    if i > 0 && table[i - 1][j] + 1 == d {
+        step(Edit::Delete, i - 1, j, out);
+    }
+}
+
+/// The functions doing an edit script. Inserts and deletes before the first
+/// and after the last kept or replaced character work on the ends of the word.
+fn to_rules(script: &[Edit]) -> Vec<Rule> {
+    let is_end = |e: &&Edit| matches!(e, Edit::Insert(_) | Edit::Delete);
+    let head = script.iter().take_while(is_end).count();
+    let tail = script[head..].iter().rev().take_while(is_end).count();
+    let (head, rest) = script.split_at(head);
+    let (middle, tail) = rest.split_at(rest.len() - tail);
+
+    let mut rules = Vec::new();
+    rules.extend(head.iter().filter(|e| matches!(e, Edit::Delete)).map(|_| Rule::TruncateLeft()));
+    rules.extend(head.iter().rev().filter_map(|e| match e {
+        Edit::Insert(c) => Some(Rule::Prepend(*c)),
+        _ => None
+    }));
+
+    let mut position = head.iter().filter(|e| matches!(e, Edit::Insert(_))).count();
+    for edit in middle {
+        match edit {
+            Edit::Keep => position += 1,
+            Edit::Replace(c) => {
+                rules.push(Rule::OverwriteAt(position, *c));
+                position += 1;
+            },
+            Edit::Insert(c) => {
+                rules.push(Rule::InsertAt(position, *c));
+                position += 1;
+            },
+            Edit::Delete => rules.push(Rule::DeleteAt(position))
+        }
+    }
+
+    rules.extend(tail.iter().filter(|e| matches!(e, Edit::Delete)).map(|_| Rule::TruncateRight()));
+    rules.extend(tail.iter().filter_map(|e| match e {
+        Edit::Insert(c) => Some(Rule::Append(*c)),
+        _ => None
+    }));
+    rules
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn printed(word: &str, password: &str) -> Vec<String> {
+        generate(word, password).iter().map(|l| printer::print_line(l, Dialect::Hashcat).unwrap()).collect()
+    }
+
+    fn words(words: &[&str]) -> Vec<String> {
+        words.iter().map(|w| w.to_string()).collect()
+    }
+
+    #[test]
+    fn edit_scripts() {
+        use Edit::*;
+        assert_eq!(to_rules(&[Insert('x'), Keep, Keep, Insert('1'), Insert('2')]), vec![Rule::Prepend('x'), Rule::Append('1'), Rule::Append('2')]);
+        assert_eq!(to_rules(&[Delete, Keep, Delete, Keep, Delete]), vec![Rule::TruncateLeft(), Rule::DeleteAt(1), Rule::TruncateRight()]);
+        assert_eq!(to_rules(&[Insert('a'), Insert('b'), Keep, Replace('c'), Insert('d'), Keep]), vec![
+            Rule::Prepend('b'), Rule::Prepend('a'), Rule::OverwriteAt(3, 'c'), Rule::InsertAt(4, 'd')
+        ]);
+    }
+
+    #[test]
+    fn derived_rules() {
+        assert_eq!(printed("password", "password1"), vec!["$1"]);
+        assert_eq!(printed("password", "Password"), vec!["c"]);
+        assert_eq!(printed("abc", "xabc"), vec!["^x"]);
+        assert_eq!(printed("password", "passwrd"), vec!["D5"]);
+        assert_eq!(printed("abc", "abd"), vec!["o2d"]);
+        assert_eq!(printed("password", "P@ssw0rd1"), vec!["c sa@ so0 $1"]);
+        for line in generate("monkey", "M0nk3y!!") {
+            assert_eq!(rule::apply_line(&line, "monkey").as_deref(), Some("M0nk3y!!"));
+        }
+    }
+
+    #[test]
+    fn base_word_lookup() {
+        let mut by_lowercase: HashMap<String, Vec<&str>> = HashMap::new();
+        for word in ["password", "Password", "pass", "monkey", "lily"] {
+            by_lowercase.entry(word.to_lowercase()).or_default().push(word);
+        }
+        assert_eq!(base_words(&by_lowercase, "P@ssw0rd123"), vec!["password", "Password"]);
+        assert_eq!(base_words(&by_lowercase, "pass99"), vec!["pass"]);
+        assert_eq!(base_words(&by_lowercase, "m0nk3y"), vec!["monkey"]);
+        // `1` for either `i` or `l`
+        assert_eq!(base_words(&by_lowercase, "1i1y"), vec!["lily"]);
+        assert!(base_words(&by_lowercase, "zzz").is_empty());
+    }
+
+    #[test]
+    fn ranking() {
+        let base = words(&["password", "monkey", "Dragon"]);
+        let cracked = words(&["password1", "monkey1", "Password", "m0nkey!", "dragon", "zzz"]);
+        let (counts, unmatched) = count_lines(&base, &cracked, 10);
+        let counts: Vec<(&str, u64)> = counts.iter().map(|(l, c)| (l.as_str(), *c)).collect();
+        assert_eq!(counts, vec![("$1", 2), ("c", 1), ("so0 $!", 1), ("l", 1)]);
+        assert_eq!(unmatched, 1);
+
+        let (counts, unmatched) = count_lines(&base, &cracked, 1);
+        assert!(counts.iter().all(|(l, _)| l != "so0 $!"));
+        assert_eq!(unmatched, 2);
+    }
+}
@@ -0,0 +1,440 @@
+use std::collections::HashSet;
+use std::io::{self, BufWriter, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::mask::{self, MaskList};
+use crate::random::Rng;
+use crate::restore::Restore;
+use crate::rule::{self, Rule};
+use crate::stats::Stats;
+use crate::wordlist::Wordlist;
+
+/// Why a run ended
+pub enum Finished {
+    /// Input exhausted or the end of the keyspace slice reached
+    Done,
+    /// Stopped by --max-candidates or --max-time, a restore point was written
+    Budget,
+    /// Stopped by Ctrl-C, a restore point was written
+    Interrupted,
+}
+
+/// Hybrid attack, masks combined with every candidate
+pub struct Hybrid {
+    pub masks: MaskList,
+    /// Mask after the candidate (-a 6) or before it (-a 7)
+    pub append: bool,
+}
+
+#[derive(Default)]
+pub struct RunOptions {
+    /// Where to periodically write the restore file, if at all
+    pub restore_path: Option<String>,
+    pub restore_timer: Duration,
+    /// Keyspace position to stop at
+    pub end: Option<u64>,
+    /// Interval of the status line, if enabled
+    pub status_timer: Option<Duration>,
+    pub max_candidates: Option<u64>,
+    pub max_time: Option<Duration>,
+    /// Number of rule lines to apply to each word, and the seed to choose them
+    pub sample: Option<(usize, u64)>,
+    /// How many times new candidates are fed back through the rules
+    pub loopback: usize,
+    /// The words are mask candidates made text by `mask::to_text`, written
+    /// back as the raw bytes of the mask
+    pub bytes: bool,
+    pub hybrid: Option<Hybrid>,
+    /// Set on Ctrl-C when a restore file is written, the run then stops
+    /// before the next candidate instead of being killed
+    pub interrupted: Option<Arc<AtomicBool>>,
+}
+
+/// Output handling shared by both iteration orders: candidates, restore points,
+/// budgets and the status line.
+struct Output<'a, W: Write> {
+    out: BufWriter<W>,
+    options: &'a RunOptions,
+    started: Instant,
+    last_save: Instant,
+    last_status: Instant,
+    /// Lines written per candidate, the size of the mask in the hybrid modes
+    mask_size: u64,
+}
+
+impl<'a, W: Write> Output<'a, W> {
+    fn new(out: W, options: &'a RunOptions) -> Output<'a, W> {
+        Output {
+            out: BufWriter::new(out),
+            options,
+            started: Instant::now(),
+            last_save: Instant::now(),
+            last_status: Instant::now(),
+            mask_size: options.hybrid.as_ref().map_or(1, |h| h.masks.keyspace()),
+        }
+    }
+
+    fn status(&mut self, stats: &Stats, offset: u64) {
+        if let Some(timer) = self.options.status_timer {
+            if self.last_status.elapsed() >= timer {
+                eprint!("\r{}", stats.status_line(offset));
+                self.last_status = Instant::now();
+            }
+        }
+    }
+
+    /// Called before the candidate at `point` is emitted. Writes the restore file
+    /// when it is due, returns why the run has to stop before the candidate, if
+    /// it has to.
+    ///
+    /// A restore point always names the next candidate to emit: everything
+    /// before it is written and flushed, nothing after it. A budget stops the
+    /// run before the first candidate over the budget, so continuing from the
+    /// restore point emits exactly the candidates a run without the budget
+    /// would have emitted next. In the hybrid modes a candidate is written
+    /// with all of the mask or not at all, the run then stops short of
+    /// --max-candidates by less than the size of the mask.
+    fn checkpoint(&mut self, point: Restore, stats: &Stats, is_new: bool) -> io::Result<Option<Finished>> {
+        let options = self.options;
+        let stop = if options.interrupted.as_ref().is_some_and(|i| i.load(Ordering::Relaxed)) {
+            Some(Finished::Interrupted)
+        } else if (is_new && options.max_candidates.is_some_and(|max| (stats.emitted + 1).saturating_mul(self.mask_size) > max))
+            || options.max_time.is_some_and(|max| self.started.elapsed() >= max) {
+            Some(Finished::Budget)
+        } else {
+            None
+        };
+
+        if let Some(path) = &options.restore_path {
+            if stop.is_some() || self.last_save.elapsed() >= options.restore_timer {
+                self.out.flush()?;
+                if let Err(e) = point.save(path) {
+                    eprintln!("Could not write restore file {}: {}", path, e);
+                }
+                self.last_save = Instant::now();
+            }
+        }
+        Ok(stop)
+    }
+
+    fn write(&mut self, candidate: &str) -> io::Result<()> {
+        match &self.options.hybrid {
+            None if self.options.bytes => {
+                self.out.write_all(&mask::to_bytes(candidate))?;
+                self.out.write_all(b"\n")
+            },
+            None => writeln!(self.out, "{}", candidate),
+            Some(hybrid) => {
+                let out = &mut self.out;
+                hybrid.masks.try_for_each(|m| {
+                    if hybrid.append {
+                        out.write_all(candidate.as_bytes())?;
+                        out.write_all(m)?;
+                    } else {
+                        out.write_all(m)?;
+                        out.write_all(candidate.as_bytes())?;
+                    }
+                    out.write_all(b"\n")
+                })
+            }
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.out.flush()
+    }
+}
+
+/// Applies the compiled rules to every word of `input`, beginning at `start`,
+/// and writes the candidates to `out`. Every word goes through all rule lines
+/// before the next word is read.
+pub fn run(rules: &[Vec<Rule>], input: &mut Wordlist, start: Restore, options: &RunOptions, stats: &mut Stats, out: impl Write) -> io::Result<Finished> {
+    let mut out = Output::new(out, options);
+    let mut selected = vec![true; rules.len()];
+
+    // Rule lines already done for the first word when restoring, in the
+    // first pass and in the loopback
+    let mut skip = start.rule;
+    let mut skip_loopback = start.loopback;
+
+    // For every line in the input
+    loop {
+        let offset = input.offset();
+        let word = input.index();
+        let line = match input.next_word() {
+            Ok(Some(line)) => line,
+            Ok(None) => break,
+            Err(e) => {
+                eprintln!("Could not read input: {}", e);
+                break;
+            }
+        };
+
+        stats.words += 1;
+        out.status(stats, offset);
+
+        // The sample only depends on the seed and the word index, so it
+        // stays the same when restoring or slicing the keyspace
+        if let Some((k, seed)) = options.sample {
+            Rng::new(seed ^ word.wrapping_mul(0x2545_F491_4F6C_DD1D)).sample(k, &mut selected);
+        }
+
+        let mut line_out = HashSet::new();
+        // Candidates new to line_out, in order, the input of the loopback
+        let mut generation = Vec::new();
+
+        // For every rule / every line in the rule file
+        for (r, rule) in rules.iter().enumerate() {
+            if let Some(end) = options.end {
+                if word * rules.len() as u64 + r as u64 >= end {
+                    out.flush()?;
+                    return Ok(Finished::Done);
+                }
+            }
+
+            if !selected[r] {
+                continue;
+            }
+
+            let candidate = match rule::apply_line(rule, &line) {
+                Some(candidate) => candidate,
+                None => {
+                    if r >= skip {
+                        stats.reject(r);
+                    }
+                    continue;
+                }
+            };
+
+            // Lines before the restore point were already emitted, they are only
+            // replayed so duplicates of them are still dropped
+            if r < skip {
+                if line_out.insert(candidate.clone()) {
+                    generation.push(candidate);
+                }
+                continue;
+            }
+
+            let is_new = !line_out.contains(&candidate);
+            let point = Restore { offset, word, rule: r, rules: rules.len(), ..Restore::default() };
+            if let Some(finished) = out.checkpoint(point, stats, is_new)? {
+                return Ok(finished);
+            }
+
+            if is_new {
+                out.write(&candidate)?;
+                generation.push(candidate.clone());
+                line_out.insert(candidate);
+                stats.emit(r);
+            } else {
+                stats.duplicate(r);
+            }
+        }
+        skip = 0;
+
+        // Feed the new candidates back through the rules. line_out spans all
+        // iterations, so cycles like `r` on its own output end quickly.
+        // Restore points inside the loopback count the rule lines applied in
+        // it, restoring replays them like the rule lines of the first pass.
+        let mut step: u64 = 0;
+        for _ in 0..options.loopback {
+            let mut next = Vec::new();
+            for plain in &generation {
+                for (r, rule) in rules.iter().enumerate() {
+                    if !selected[r] {
+                        continue;
+                    }
+                    step += 1;
+
+                    let candidate = match rule::apply_line(rule, plain) {
+                        Some(candidate) => candidate,
+                        None => {
+                            if step > skip_loopback {
+                                stats.reject(r);
+                            }
+                            continue;
+                        }
+                    };
+
+                    if step <= skip_loopback {
+                        if line_out.insert(candidate.clone()) {
+                            next.push(candidate);
+                        }
+                        continue;
+                    }
+
+                    let is_new = !line_out.contains(&candidate);
+                    let point = Restore { offset, word, rule: rules.len(), rules: rules.len(), loopback: step - 1, ..Restore::default() };
+                    if let Some(finished) = out.checkpoint(point, stats, is_new)? {
+                        return Ok(finished);
+                    }
+
+                    if is_new {
+                        out.write(&candidate)?;
+                        next.push(candidate.clone());
+                        line_out.insert(candidate);
+                        stats.emit(r);
+                    } else {
+                        stats.duplicate(r);
+                    }
+                }
+            }
+            if next.is_empty() {
+                break;
+            }
+            generation = next;
+        }
+        skip_loopback = 0;
+    }
+    out.flush()?;
+    Ok(Finished::Done)
+}
+
+/// Applies the compiled rules rule line by rule line: the first rule line to
+/// every word of `input`, then the second and so on. `input` is rewound for
+/// every rule line, `words` is the number of words in it.
+///
+/// Candidates are not deduplicated in this order, as that would require
+/// keeping every candidate of every word around.
+pub fn run_rule_major(rules: &[Vec<Rule>], input: &mut Wordlist, words: u64, start: Restore, options: &RunOptions, stats: &mut Stats, out: impl Write) -> io::Result<Finished> {
+    let mut out = Output::new(out, options);
+    let size = input.size().unwrap_or(0);
+
+    for (r, rule) in rules.iter().enumerate().skip(start.rule) {
+        // The first pass may start in the middle of the input when restoring
+        if r != start.rule {
+            input.seek(0, 0)?;
+        }
+
+        loop {
+            let offset = input.offset();
+            let word = input.index();
+
+            if let Some(end) = options.end {
+                if r as u64 * words + word >= end {
+                    out.flush()?;
+                    return Ok(Finished::Done);
+                }
+            }
+
+            let line = match input.next_word() {
+                Ok(Some(line)) => line,
+                Ok(None) => break,
+                Err(e) => {
+                    eprintln!("Could not read input: {}", e);
+                    break;
+                }
+            };
+
+            if r == start.rule {
+                stats.words += 1;
+            }
+            // Progress is measured over all passes through the input
+            out.status(stats, r as u64 * size + offset);
+
+            let point = Restore { offset, word, rule: r, rules: rules.len(), rule_major: true, ..Restore::default() };
+            if let Some(finished) = out.checkpoint(point, stats, true)? {
+                return Ok(finished);
+            }
+
+            match rule::apply_line(rule, &line) {
+                Some(candidate) => {
+                    out.write(&candidate)?;
+                    stats.emit(r);
+                },
+                None => stats.reject(r)
+            }
+        }
+    }
+    out.flush()?;
+    Ok(Finished::Done)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WORDS: &str = "password\nletmein\nabc\nPassword\nsummer\n";
+    const RULES: &str = ":\n$1\nu\nr\nc\n$1 u\nl\n";
+
+    fn options(restore_path: Option<String>) -> RunOptions {
+        // Only the stop at the budget writes the restore file
+        RunOptions { restore_path, restore_timer: Duration::from_secs(3600), ..RunOptions::default() }
+    }
+
+    /// Runs from `start` like main does, returns the output
+    fn output(rules: &[Vec<Rule>], start: Restore, options: &RunOptions) -> (Vec<u8>, Finished) {
+        let mut input = Wordlist::memory(WORDS);
+        input.seek(start.offset, start.word).unwrap();
+        let mut stats = Stats::new(rules.len(), None, 0);
+        let mut out = Vec::new();
+        let finished = match start.rule_major {
+            true => run_rule_major(rules, &mut input, WORDS.lines().count() as u64, start, options, &mut stats, &mut out),
+            false => run(rules, &mut input, start, options, &mut stats, &mut out)
+        }.unwrap();
+        (out, finished)
+    }
+
+    /// Stops the run after every possible number of candidates and resumes it
+    /// from the restore file, the two parts must add up to the full output.
+    /// `options` makes the options of the mode tested from a restore path.
+    fn check_resume(name: &str, rule_major: bool, options: impl Fn(Option<String>) -> RunOptions) {
+        let rules = crate::parse_rules(RULES, false);
+        let start = Restore { rules: rules.len(), rule_major, ..Restore::default() };
+        let (full, _) = output(&rules, start, &options(None));
+        let path = std::env::temp_dir().join(format!("hcre-rs-{}-{}.restore", name, std::process::id()));
+        let path = path.to_str().unwrap().to_string();
+
+        let lines = full.iter().filter(|b| **b == b'\n').count() as u64;
+        for n in 0..lines {
+            let budget = RunOptions { max_candidates: Some(n), ..options(Some(path.clone())) };
+            let (head, finished) = output(&rules, start, &budget);
+            assert!(matches!(finished, Finished::Budget));
+
+            let (tail, finished) = output(&rules, Restore::load(&path).unwrap(), &options(None));
+            assert!(matches!(finished, Finished::Done));
+            assert_eq!([head, tail].concat(), full, "stopped after {} candidates", n);
+        }
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn resume_emits_nothing_twice() {
+        check_resume("resume", false, options);
+    }
+
+    #[test]
+    fn resume_loopback() {
+        check_resume("loopback", false, |path| RunOptions { loopback: 2, ..options(path) });
+    }
+
+    #[test]
+    fn resume_hybrid() {
+        check_resume("hybrid", false, |path| {
+            let masks = MaskList::new(vec![mask::Mask::parse("?d", &Default::default()).unwrap()]);
+            RunOptions { hybrid: Some(Hybrid { masks, append: true }), ..options(path) }
+        });
+    }
+
+    #[test]
+    fn resume_rule_major() {
+        check_resume("rule-major", true, options);
+    }
+
+    #[test]
+    fn resume_sampled_rules() {
+        check_resume("sample", false, |path| RunOptions { sample: Some((3, 7)), ..options(path) });
+    }
+
+    #[test]
+    fn restore_file_round_trip() {
+        let path = std::env::temp_dir().join(format!("hcre-rs-round-trip-{}.restore", std::process::id()));
+        let path = path.to_str().unwrap();
+        let point = Restore { offset: 42, word: 7, rule: 3, rules: 9, rule_major: true, loopback: 5 };
+        point.save(path).unwrap();
+        assert_eq!(Restore::load(path).unwrap(), point);
+        let _ = std::fs::remove_file(path);
+    }
+}
@@ -0,0 +1,195 @@
+// Algebraic simplification of rule lines
+//
+// Rewrites pairs of adjacent functions using identities that hold for every
+// word: inverse pairs (`r r`, `{ }`, `$X ]`) and idempotent functions (`l l`,
+// `@X @X`). Case functions map Unicode characters, where `ß` becomes `SS`, so
+// only the case identities that hold for every character are used: `t t` or
+// `l u` change words like `ß` and `İ`, `u C` is `C` for every word.
+
+use std::fs;
+
+use crate::jtr;
+use crate::printer;
+use crate::rule::{Dialect, Rule};
+use crate::source::{self, Options, Shared};
+
+/// The shortest form of `line` the identities lead to
+pub fn simplify(line: &[Rule]) -> Vec<Rule> {
+    let mut out: Vec<Rule> = line.iter().filter(|r| **r != Rule::Nothing && !is_noop(r)).cloned().collect();
+
+    let mut i = 0;
+    while i + 1 < out.len() {
+        match pair(&out[i], &out[i + 1]) {
+            Some(replacement) => {
+                out.splice(i..i + 2, replacement);
+                // The replacement may form a new pair with the function before it
+                i = i.saturating_sub(1);
+            },
+            None => i += 1
+        }
+    }
+    out
+}
+
+/// Functions that never change a word
+fn is_noop(rule: &Rule) -> bool {
+    matches!(rule, Rule::Replace(x, y) if x == y)
+}
+
+/// What two adjacent functions can be replaced with, if anything
+fn pair(a: &Rule, b: &Rule) -> Option<Vec<Rule>> {
+    let replacement = match (a, b) {
+        // Inverse pairs
+        (Rule::Reverse(), Rule::Reverse()) => vec![],
+        (Rule::RotateLeft(), Rule::RotateRight()) | (Rule::RotateRight(), Rule::RotateLeft()) => vec![],
+        (Rule::Append(_), Rule::TruncateRight()) => vec![],
+        (Rule::Prepend(_), Rule::TruncateLeft()) => vec![],
+
+        // Idempotent functions
+        (Rule::Purge(x), Rule::Purge(y)) if x == y => vec![a.clone()],
+        (Rule::Lowercase(), Rule::Lowercase()) | (Rule::Uppercase(), Rule::Uppercase()) => vec![a.clone()],
+        (Rule::Capitalize(), Rule::Capitalize()) => vec![a.clone()],
+
+        // Case rules overwritten by a later one. Only `u` before `c` or `C`
+        // is, `l u` changes İ and `c l` changes µ
+        (Rule::Uppercase(), Rule::Capitalize()) => vec![Rule::Uppercase()],
+        (Rule::Uppercase(), Rule::InvertCapitalize()) => vec![Rule::InvertCapitalize()],
+        _ => return None
+    };
+    Some(replacement)
+}
+
+/// Options shared with the other subcommands
+const SHARED: &[Shared] = &[];
+
+fn print_usage(program: &str) {
+    eprintln!("Usage: {} simplify [options] <rule file>", program);
+    eprintln!("Prints the hashcat rule file with every rule line simplified.");
+    eprintln!("Options:");
+    eprintln!("  -d                            Enable debug output.");
+    source::print_common_usage(SHARED);
+}
+
+pub fn main(program: &str, args: &[String]) {
+    let mut debug = false;
+
+    let Options { paths, .. } = source::parse_args(program, args, print_usage, SHARED, |arg, _| {
+        match arg {
+            "-d" => debug = true,
+            _ => return false
+        }
+        true
+    });
+
+    let path = match paths.as_slice() {
+        [path] => path,
+        _ => {
+            print_usage(program);
+            std::process::exit(1);
+        }
+    };
+    let content = match fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(e) => {
+            eprintln!("Could not read file {}: {}", path, e);
+            std::process::exit(1);
+        }
+    };
+    if jtr::is_jtr(&content) {
+        eprintln!("{} is a JtR rule file, only hashcat rule files can be simplified.", path);
+        std::process::exit(1);
+    }
+
+    let mut lines = 0;
+    let mut simplified = 0;
+    for (i, line) in content.lines().enumerate() {
+        if line.trim().is_empty() || line.starts_with('#') {
+            println!("{}", line);
+            continue;
+        }
+        lines += 1;
+
+        // Lines with functions the parser skips are kept as they are
+        let (rules, skipped) = crate::parse_rules_checked(line, debug).swap_remove(0);
+        let mut shorter = simplify(&rules);
+        // A line simplified away keeps the word
+        if shorter.is_empty() {
+            shorter.push(Rule::Nothing);
+        }
+        if !skipped.is_empty() || shorter == rules {
+            println!("{}", line);
+            continue;
+        }
+        match printer::print_line(&shorter, Dialect::Hashcat) {
+            Ok(printed) => {
+                if debug {
+                    eprintln!("[line {}] {} -> {}", i + 1, line, printed);
+                }
+                simplified += 1;
+                println!("{}", printed);
+            },
+            Err(_) => println!("{}", line)
+        }
+    }
+    eprintln!("Simplified {} of {} rule lines.", simplified, lines);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dedupe::PROBES;
+    use crate::rule::apply_line;
+
+    /// Functions covering every identity, and some without one
+    fn functions() -> Vec<Rule> {
+        vec![
+            Rule::Nothing, Rule::Lowercase(), Rule::Uppercase(), Rule::Capitalize(), Rule::InvertCapitalize(),
+            Rule::ToggleCase(), Rule::ToggleAt(0), Rule::Reverse(), Rule::RotateLeft(), Rule::RotateRight(),
+            Rule::Append('a'), Rule::Prepend('ß'), Rule::TruncateLeft(), Rule::TruncateRight(),
+            Rule::Purge('a'), Rule::Replace('a', 'a'), Rule::Replace('s', 'ß'), Rule::Duplicate(),
+        ]
+    }
+
+    fn assert_equivalent(line: &[Rule]) {
+        let simplified = simplify(line);
+        for probe in PROBES {
+            assert_eq!(apply_line(&simplified, probe), apply_line(line, probe), "{:?} -> {:?} on {}", line, simplified, probe);
+        }
+    }
+
+    #[test]
+    fn simplified_lines_are_equivalent() {
+        let functions = functions();
+        for a in &functions {
+            for b in &functions {
+                for c in &functions {
+                    assert_equivalent(&[a.clone(), b.clone(), c.clone()]);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn simplifies() {
+        assert_eq!(simplify(&[Rule::Reverse(), Rule::Append('1'), Rule::TruncateRight(), Rule::Reverse()]), []);
+        assert_eq!(simplify(&[Rule::Uppercase(), Rule::Nothing, Rule::Capitalize(), Rule::Uppercase()]), [Rule::Uppercase()]);
+        // Not for every character, `t t` turns ß into ss
+        assert_eq!(simplify(&[Rule::ToggleCase(), Rule::ToggleCase()]).len(), 2);
+    }
+
+    #[test]
+    fn overwritten_case() {
+        let line = [Rule::Uppercase(), Rule::InvertCapitalize()];
+        assert_eq!(simplify(&line), [Rule::InvertCapitalize()]);
+        for c in (0..0x110000).filter_map(char::from_u32) {
+            let word = format!("{}ßa", c);
+            assert_eq!(apply_line(&line, &word), apply_line(&[Rule::InvertCapitalize()], &word), "{}", word);
+        }
+        // Kept, the first rule changes what the second one does on İ and µ
+        for (a, b) in [(Rule::Lowercase(), Rule::Uppercase()), (Rule::Capitalize(), Rule::Lowercase()), (Rule::ToggleCase(), Rule::Uppercase())] {
+            let line = [a, b];
+            assert_eq!(simplify(&line).len(), 2);
+            assert!(["İ", "µ"].iter().any(|w| apply_line(&line, w) != apply_line(&line[1..], w)));
+        }
+    }
+}
@@ -0,0 +1,215 @@
+// Rule files read line by line, keeping where every rule line came from, and
+// the command line options of the subcommands working on them
+
+use std::fs;
+
+use crate::jtr;
+use crate::rule::{self, Rule};
+
+/// A line of a rule file and its rule lines, more than one for JtR lines
+/// using the preprocessor
+pub struct Line {
+    pub path: String,
+    /// Line number in the file, starting at 1
+    pub number: usize,
+    pub text: String,
+    pub rules: Vec<Vec<Rule>>,
+    /// The parser skipped functions, the rules don't do what the line says
+    pub incomplete: bool,
+    /// Name of the `[List.Rules:...]` section of JtR lines, None for hashcat lines
+    pub section: Option<String>,
+}
+
+impl Line {
+    /// The candidates of every rule line for `word`, rejected ones left out
+    pub fn apply<'a>(&'a self, word: &'a str) -> impl Iterator<Item = String> + 'a {
+        self.rules.iter().filter_map(move |rules| rule::apply_line(rules, word))
+    }
+}
+
+/// The rule lines of a hashcat or JtR rule file, without comments
+pub fn read_lines(path: &str, content: &str, section: Option<&str>) -> Vec<Line> {
+    let mut out = Vec::new();
+    if jtr::is_jtr(content) {
+        // Section of every line of the file
+        let mut current = None;
+        let names: Vec<Option<&str>> = content.lines().map(|l| {
+            if jtr::section_header(l).is_some_and(|h| h.starts_with("list.rules:")) {
+                let l = l.trim_end();
+                current = Some(&l["[List.Rules:".len()..l.len() - 1]);
+            }
+            current
+        }).collect();
+
+        for (i, text) in jtr::section_lines(content, section) {
+            if jtr::is_comment(text) {
+                continue;
+            }
+            let compiled = jtr::compile_line(text);
+            out.push(Line {
+                path: path.to_string(),
+                number: i + 1,
+                text: text.to_string(),
+                incomplete: compiled.is_err(),
+                rules: compiled.unwrap_or_default(),
+                section: names[i].map(str::to_string),
+            });
+        }
+    } else {
+        for (i, text) in content.lines().enumerate() {
+            if text.trim().is_empty() || text.starts_with('#') {
+                continue;
+            }
+            let (rules, skipped) = crate::parse_rules_checked(text, false).swap_remove(0);
+            out.push(Line {
+                path: path.to_string(),
+                number: i + 1,
+                text: text.to_string(),
+                rules: vec![rules],
+                incomplete: !skipped.is_empty(),
+                section: None,
+            });
+        }
+    }
+    out
+}
+
+/// The rule lines of all files in `paths`, exits if one cannot be read
+pub fn read_files(paths: &[String], section: Option<&str>) -> Vec<Line> {
+    let mut lines = Vec::new();
+    for path in paths {
+        match fs::read_to_string(path) {
+            Ok(content) => lines.extend(read_lines(path, &content, section)),
+            Err(e) => {
+                eprintln!("Could not read file {}: {}", path, e);
+                std::process::exit(1);
+            }
+        }
+    }
+    lines
+}
+
+/// The section header to print before rule lines taken from `lines`, so the
+/// output reads back in the dialect of the input. JtR lines go into the
+/// section of the first one, None for hashcat lines. Exits if both dialects are
+/// mixed, no rule file can hold them.
+pub fn header(lines: &[Line]) -> Option<String> {
+    if lines.iter().any(|l| l.section.is_some()) && lines.iter().any(|l| l.section.is_none()) {
+        eprintln!("Cannot write hashcat and JtR rule lines into one rule file.");
+        std::process::exit(1);
+    }
+    lines.first()?.section.as_ref().map(|s| format!("[List.Rules:{}]", s))
+}
+
+/// Options of the subcommands working on rule files, the ones they share are
+/// `-w`, `--report`, `--jtr-section` and the arguments that are no options
+#[derive(Default)]
+pub struct Options {
+    /// Arguments that are no options, usually rule files
+    pub paths: Vec<String>,
+    /// Every `-w`
+    pub wordlists: Vec<String>,
+    pub report: Option<String>,
+    pub section: Option<String>,
+}
+
+/// An option a subcommand shares with others, `-h` is always known
+#[derive(PartialEq)]
+pub enum Shared {
+    /// `-w <path to wordlist>`
+    Wordlist,
+    /// `--report <path>`
+    Report,
+    /// `--jtr-section <name>`
+    Section,
+}
+
+/// The arguments of a subcommand while they are parsed
+pub struct Args<'a> {
+    args: &'a [String],
+    i: usize,
+}
+
+impl Args<'_> {
+    /// Value of the current option, exits if it is missing. `what` and
+    /// `usage` make the message, like `Missing <what>. Usage: <usage>.`
+    pub fn value(&mut self, what: &str, usage: &str) -> String {
+        self.i += 1;
+        match self.args.get(self.i) {
+            Some(value) => value.clone(),
+            None => {
+                eprintln!("Missing {}. Usage: {}.", what, usage);
+                std::process::exit(1);
+            }
+        }
+    }
+
+    /// Numeric value of the current option `option`, exits if it is missing or invalid
+    pub fn number(&mut self, option: &str) -> u64 {
+        self.i += 1;
+        match self.args.get(self.i).and_then(|a| a.parse().ok()) {
+            Some(n) => n,
+            None => {
+                eprintln!("Missing or invalid value. Usage: {} <n>.", option);
+                std::process::exit(1);
+            }
+        }
+    }
+
+    /// Raw value of the current option, `None` if it is missing
+    pub fn next(&mut self) -> Option<&str> {
+        self.i += 1;
+        self.args.get(self.i).map(|a| a.as_str())
+    }
+}
+
+/// Help of the `shared` options and `-h`, printed last
+pub fn print_common_usage(shared: &[Shared]) {
+    if shared.contains(&Shared::Section) {
+        eprintln!("  --jtr-section <name>          Only use the [List.Rules:<name>] section of JtR rule files.");
+    }
+    eprintln!("  -h                            Display this help message.");
+}
+
+/// Parses the arguments of a subcommand. `own` gets every option first and
+/// returns false for the ones it doesn't know, those are parsed as the
+/// `shared` options or rejected. Everything after `--` is no option.
+pub fn parse_args(program: &str, args: &[String], print_usage: fn(&str), shared: &[Shared], mut own: impl FnMut(&str, &mut Args) -> bool) -> Options {
+    let mut options = Options::default();
+    let mut parser = Args { args, i: 0 };
+
+    while parser.i < args.len() {
+        let arg = args[parser.i].as_str();
+        if !own(arg, &mut parser) {
+            match arg {
+                "-w" if shared.contains(&Shared::Wordlist) => {
+                    options.wordlists.push(parser.value("wordlist location", "-w <path to wordlist>"));
+                },
+                "--report" if shared.contains(&Shared::Report) => {
+                    options.report = Some(parser.value("report location", "--report <path>"));
+                },
+                "--jtr-section" if shared.contains(&Shared::Section) => {
+                    options.section = Some(parser.value("section name", "--jtr-section <name>"));
+                },
+                "-h" => {
+                    print_usage(program);
+                    std::process::exit(0);
+                },
+                "--" => {
+                    options.paths.extend(args[parser.i + 1..].iter().cloned());
+                    break;
+                },
+                arg if !arg.starts_with('-') || arg == "-" => {
+                    options.paths.push(arg.to_string());
+                },
+                arg => {
+                    eprintln!("Unknown argument {}.", arg);
+                    print_usage(program);
+                    std::process::exit(1);
+                }
+            }
+        }
+        parser.i += 1;
+    }
+    options
+}
@@ -0,0 +1,143 @@
+use std::time::{Duration, Instant};
+
+/// Counters for a single rule line
+#[derive(Debug, Default, Clone, Copy)]
+pub struct LineStats {
+    pub emitted: u64,
+    pub duplicates: u64,
+    pub rejected: u64,
+}
+
+/// Counters collected during a run, used for the status line and the final summary.
+pub struct Stats {
+    pub words: u64,
+    pub emitted: u64,
+    pub duplicates: u64,
+    pub rejected: u64,
+    pub lines: Vec<LineStats>,
+    started: Instant,
+    /// Size of the input in bytes, if known
+    input_size: Option<u64>,
+    /// Byte offset the run started at, non zero when restoring or skipping
+    start_offset: u64,
+}
+
+impl Stats {
+    pub fn new(line_count: usize, input_size: Option<u64>, start_offset: u64) -> Stats {
+        Stats {
+            words: 0,
+            emitted: 0,
+            duplicates: 0,
+            rejected: 0,
+            lines: vec![LineStats::default(); line_count],
+            started: Instant::now(),
+            input_size,
+            start_offset,
+        }
+    }
+
+    pub fn emit(&mut self, line: usize) {
+        self.emitted += 1;
+        self.lines[line].emitted += 1;
+    }
+
+    pub fn duplicate(&mut self, line: usize) {
+        self.duplicates += 1;
+        self.lines[line].duplicates += 1;
+    }
+
+    /// A reject rule dropped the candidate of `line`
+    pub fn reject(&mut self, line: usize) {
+        self.rejected += 1;
+        self.lines[line].rejected += 1;
+    }
+
+    /// Candidates per second since the start of the run
+    fn rate(&self) -> f64 {
+        let elapsed = self.started.elapsed().as_secs_f64();
+        if elapsed > 0.0 {
+            self.emitted as f64 / elapsed
+        } else {
+            0.0
+        }
+    }
+
+    /// Single line status, `offset` is the current byte position in the input.
+    pub fn status_line(&self, offset: u64) -> String {
+        let mut status = format!(
+            "Words: {} | Candidates: {} | Duplicates: {} | {:.0} c/s",
+            self.words, self.emitted, self.duplicates, self.rate()
+        );
+
+        if let Some(size) = self.input_size.filter(|s| *s > 0) {
+            let progress = (offset as f64 / size as f64).min(1.0);
+            status.push_str(&format!(" | Progress: {:.2}%", progress * 100.0));
+
+            // Only the part of the input covered by this run says anything about its speed
+            let done = offset.saturating_sub(self.start_offset) as f64;
+            let left = size.saturating_sub(offset) as f64;
+            if done > 0.0 {
+                let remaining = self.started.elapsed().as_secs_f64() * left / done;
+                status.push_str(&format!(" | ETA: {}", format_duration(Duration::from_secs_f64(remaining))));
+            }
+        }
+        status
+    }
+
+    /// Prints the end of run summary, including the counts of every rule line, to stderr.
+    pub fn print_summary(&self) {
+        eprintln!("Words read:         {}", self.words);
+        eprintln!("Candidates emitted: {}", self.emitted);
+        eprintln!("Duplicates dropped: {}", self.duplicates);
+        eprintln!("Rejected:           {}", self.rejected);
+        eprintln!("Time:               {} ({:.0} c/s)", format_duration(self.started.elapsed()), self.rate());
+        eprintln!();
+        eprintln!("{:>8} {:>14} {:>14} {:>14}", "Line", "Emitted", "Duplicates", "Rejected");
+        for (i, line) in self.lines.iter().enumerate() {
+            eprintln!("{:>8} {:>14} {:>14} {:>14}", i + 1, line.emitted, line.duplicates, line.rejected);
+        }
+    }
+}
+
+/// Formats a duration as `[Hh ][Mm ]Ss`
+pub fn format_duration(d: Duration) -> String {
+    let secs = d.as_secs();
+    let (h, m, s) = (secs / 3600, secs / 60 % 60, secs % 60);
+    if h > 0 {
+        format!("{}h {}m {}s", h, m, s)
+    } else if m > 0 {
+        format!("{}m {}s", m, s)
+    } else {
+        format!("{}s", s)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::run::{self, RunOptions};
+    use crate::wordlist::Wordlist;
+
+    #[test]
+    fn counters_after_a_run() {
+        let rules = crate::parse_rules(":\nu\nl\n_4", false);
+        assert_eq!(rules.len(), 4);
+        let mut input = Wordlist::memory("abc\nABC\n");
+        let mut stats = Stats::new(rules.len(), None, 0);
+        let mut out = Vec::new();
+        run::run(&rules, &mut input, Default::default(), &RunOptions::default(), &mut stats, &mut out).unwrap();
+
+        assert_eq!(String::from_utf8(out).unwrap(), "abc\nABC\nABC\nabc\n");
+        assert_eq!((stats.words, stats.emitted, stats.duplicates, stats.rejected), (2, 4, 2, 2));
+        let lines: Vec<(u64, u64, u64)> = stats.lines.iter().map(|l| (l.emitted, l.duplicates, l.rejected)).collect();
+        assert_eq!(lines, [(2, 0, 0), (1, 1, 0), (1, 1, 0), (0, 0, 2)]);
+        assert!(stats.status_line(0).starts_with("Words: 2 | Candidates: 4 | Duplicates: 2 |"));
+    }
+
+    #[test]
+    fn durations() {
+        assert_eq!(format_duration(Duration::from_secs(59)), "59s");
+        assert_eq!(format_duration(Duration::from_secs(61)), "1m 1s");
+        assert_eq!(format_duration(Duration::from_secs(3 * 3600 + 5)), "3h 0m 5s");
+    }
+}
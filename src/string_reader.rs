@@ -33,16 +33,9 @@ impl StringReader {
     }
 
     pub fn skip_line(&mut self) -> Result<(), &str> {
-        loop {
-            match self.read() {
-                Ok(c) => {
-                    if c == '\n' {
-                        break;
-                    }
-                },
-                Err(_) => {
-                    break;
-                }
+        while let Ok(c) = self.read() {
+            if c == '\n' {
+                break;
             }
         }
         Ok(())
@@ -0,0 +1,334 @@
+// Rule templates, expanded into plain rule lines before parsing
+//
+// `[...]` is a character class (`[ae]`, `[0-9]`), `?l`, `?u`, `?d`, `?s`, `?a`,
+// `?h` and `?H` are the charsets known from masks, and a number range
+// (`[1950-2030]`) directly after `$` or `^` appends or prepends every number,
+// zero padded to the width of the lower bound if it starts with 0 (`[00-99]`).
+// `\` escapes the next character, e.g. `\[` for the `[` rule.
+//
+// JtR rule files only know character classes and escapes, as `?` is part of
+// their rule syntax. `\p` in front of a class makes it advance together with
+// all classes before it, `\p1` to `\p9` with the class of that number and
+// `\p0` with the one right before it, e.g. `$[0-2]\p0[a-c]` is `$0a`, `$1b`
+// and `$2c`.
+
+use crate::mask;
+use crate::rule::Dialect;
+
+enum Segment {
+    Literal(String),
+    /// One of the alternatives is picked for every expanded line. Choices of
+    /// the same group pick the alternative at the same index.
+    Choice(Vec<String>, usize),
+}
+
+/// Expands every template line of `rules`. Returns the expanded rules, the
+/// number of template lines and the number of lines they expanded into.
+pub fn expand(rules: &str) -> Result<(String, usize, usize), String> {
+    let mut out = String::new();
+    let mut templates = 0;
+    let mut expanded_lines = 0;
+    for (i, line) in rules.lines().enumerate() {
+        if line.starts_with('#') {
+            out.push_str(line);
+            out.push('\n');
+            continue;
+        }
+
+        let lines = expand_line(line, Dialect::Hashcat).map_err(|e| format!("[line {}] {}", i + 1, e))?;
+        if lines.len() != 1 || lines[0] != line {
+            templates += 1;
+            expanded_lines += lines.len();
+        }
+        for expanded in lines {
+            out.push_str(&expanded);
+            out.push('\n');
+        }
+    }
+    Ok((out, templates, expanded_lines))
+}
+
+/// Expands a single template line into all of its rule lines.
+pub fn expand_line(line: &str, dialect: Dialect) -> Result<Vec<String>, String> {
+    Ok(product(&parse_line(line, dialect)?))
+}
+
+fn parse_line(line: &str, dialect: Dialect) -> Result<Vec<Segment>, String> {
+    let mut segments = Vec::new();
+    let mut literal = String::new();
+    let mut chars = line.chars();
+    // Group of every choice so far, and the one `\p` set for the next
+    let mut groups: Vec<usize> = Vec::new();
+    let mut parallel: Option<usize> = None;
+
+    while let Some(c) = chars.next() {
+        if parallel.is_some() && c != '[' {
+            return Err("\\p must be followed by a character class".to_string());
+        }
+        match c {
+            '\\' => {
+                let escaped = chars.next().ok_or("Line ends with a single \\")?;
+                if dialect == Dialect::Jtr && escaped == 'p' {
+                    let group = match chars.clone().next().and_then(|n| n.to_digit(10)) {
+                        Some(n) => {
+                            chars.next();
+                            let range = if n == 0 { groups.len().checked_sub(1) } else { Some(n as usize - 1) };
+                            range.and_then(|r| groups.get(r)).copied().ok_or_else(|| format!("No character class {} for \\p{}", n, n))?
+                        },
+                        // All classes so far join the group of the first one
+                        None => {
+                            let first = *groups.first().ok_or("No character class before \\p")?;
+                            for segment in &mut segments {
+                                if let Segment::Choice(_, group) = segment {
+                                    *group = first;
+                                }
+                            }
+                            groups.iter_mut().for_each(|g| *g = first);
+                            first
+                        }
+                    };
+                    parallel = Some(group);
+                    continue;
+                }
+                if dialect == Dialect::Jtr && (escaped == 'r' || escaped.is_ascii_digit()) {
+                    return Err(format!("Preprocessor command \\{} is not supported", escaped));
+                }
+                literal.push(escaped);
+            },
+            '?' if dialect == Dialect::Hashcat => {
+                let class = chars.next().ok_or("Line ends with a single ?")?;
+                let alternatives = match class {
+                    '?' => vec!['?'],
+                    // Control characters would break the rule line
+                    'b' => return Err("?b cannot be used in rule templates".to_string()),
+                    _ => mask::builtin(class).ok_or_else(|| format!("Unknown charset ?{}", class))?.into_iter().map(char::from).collect()
+                };
+                groups.push(groups.len());
+                segments.push(Segment::Literal(std::mem::take(&mut literal)));
+                segments.push(Segment::Choice(alternatives.iter().map(|c| c.to_string()).collect(), groups.len() - 1));
+            },
+            '[' => {
+                let mut content = String::new();
+                loop {
+                    match chars.next() {
+                        Some(']') => break,
+                        Some('\\') => {
+                            content.push('\\');
+                            content.push(chars.next().ok_or("Line ends with a single \\")?);
+                        },
+                        Some(c) => content.push(c),
+                        None => return Err("Unterminated [ (use \\[ for the [ rule)".to_string())
+                    }
+                }
+
+                let range = if dialect == Dialect::Hashcat { number_range(&content) } else { None };
+                let alternatives = match range {
+                    Some((from, to, width)) => {
+                        // Numbers are spelled out with the function in front of them
+                        let function = match literal.chars().last() {
+                            Some(f @ ('$' | '^')) => f,
+                            _ => return Err(format!("Number range [{}] must follow $ or ^", content))
+                        };
+                        if from > to {
+                            return Err(format!("Invalid number range [{}]", content));
+                        }
+                        (from..=to).map(|n| spell_number(n, width, function)).collect()
+                    },
+                    None => char_class(&content)?.iter().map(|c| c.to_string()).collect()
+                };
+                groups.push(parallel.take().unwrap_or(groups.len()));
+                segments.push(Segment::Literal(std::mem::take(&mut literal)));
+                segments.push(Segment::Choice(alternatives, groups[groups.len() - 1]));
+            },
+            _ => literal.push(c)
+        }
+    }
+    if parallel.is_some() {
+        return Err("\\p must be followed by a character class".to_string());
+    }
+    segments.push(Segment::Literal(literal));
+
+    let mut lengths: Vec<Option<usize>> = vec![None; groups.len()];
+    for segment in &segments {
+        if let Segment::Choice(alternatives, group) = segment {
+            if lengths[*group].is_some_and(|l| l != alternatives.len()) {
+                return Err("Parallel character classes must have the same length".to_string());
+            }
+            lengths[*group] = Some(alternatives.len());
+        }
+    }
+    Ok(segments)
+}
+
+/// `from-to` where at least one of the numbers has more than one digit,
+/// single digits like `0-9` are a character class. The third value is the
+/// width to pad the numbers to with zeros, set by a leading zero in `from`
+/// as in `00-99`.
+fn number_range(content: &str) -> Option<(u64, u64, usize)> {
+    let (from, to) = content.split_once('-')?;
+    if from.is_empty() || to.is_empty() || !from.chars().chain(to.chars()).all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    if from.len() == 1 && to.len() == 1 {
+        return None;
+    }
+    let width = if from.len() > 1 && from.starts_with('0') { from.len() } else { 0 };
+    Some((from.parse().ok()?, to.parse().ok()?, width))
+}
+
+/// Number `n`, padded with zeros to `width` digits, as a sequence of `$` or
+/// `^` functions. The function in front of the first digit is already part of
+/// the line. Prepending goes backwards.
+fn spell_number(n: u64, width: usize, function: char) -> String {
+    let number = format!("{:0width$}", n, width = width);
+    let digits: Vec<char> = if function == '^' {
+        number.chars().rev().collect()
+    } else {
+        number.chars().collect()
+    };
+
+    let mut out = String::new();
+    for (i, d) in digits.iter().enumerate() {
+        if i > 0 {
+            out.push(function);
+        }
+        out.push(*d);
+    }
+    out
+}
+
+/// Characters of a class like `a-z0-9_`, `\` escapes the next character
+fn char_class(content: &str) -> Result<Vec<char>, String> {
+    let mut chars: Vec<(char, bool)> = Vec::new();
+    let mut it = content.chars();
+    while let Some(c) = it.next() {
+        match c {
+            '\\' => chars.push((it.next().unwrap(), true)),
+            _ => chars.push((c, false))
+        }
+    }
+
+    let mut out: Vec<char> = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let (c, _) = chars[i];
+        // An unescaped - between two characters is a range
+        if i + 2 < chars.len() && chars[i + 1] == ('-', false) {
+            let end = chars[i + 2].0;
+            if end < c {
+                return Err(format!("Invalid range {}-{} in [{}]", c, end, content));
+            }
+            for r in c..=end {
+                if !out.contains(&r) {
+                    out.push(r);
+                }
+            }
+            i += 3;
+        } else {
+            if !out.contains(&c) {
+                out.push(c);
+            }
+            i += 1;
+        }
+    }
+
+    if out.is_empty() {
+        return Err("Empty character class []".to_string());
+    }
+    Ok(out)
+}
+
+/// Every combination of the alternatives, the first choice changes slowest.
+/// Choices of a group count as one.
+fn product(segments: &[Segment]) -> Vec<String> {
+    // Length of every group, in the order they first appear
+    let mut groups: Vec<(usize, usize)> = Vec::new();
+    for segment in segments {
+        if let Segment::Choice(alternatives, group) = segment {
+            if !groups.iter().any(|(g, _)| g == group) {
+                groups.push((*group, alternatives.len()));
+            }
+        }
+    }
+
+    let total: usize = groups.iter().map(|(_, len)| len).product();
+    (0..total).map(|mut n| {
+        let mut picks = vec![0; groups.len()];
+        for (pick, (_, len)) in picks.iter_mut().zip(&groups).rev() {
+            *pick = n % len;
+            n /= len;
+        }
+        segments.iter().map(|segment| match segment {
+            Segment::Literal(l) => l.as_str(),
+            Segment::Choice(alternatives, group) => {
+                let i = groups.iter().position(|(g, _)| g == group).unwrap();
+                alternatives[picks[i]].as_str()
+            }
+        }).collect()
+    }).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lines(template: &str) -> Vec<String> {
+        expand_line(template, Dialect::Hashcat).unwrap()
+    }
+
+    #[test]
+    fn classes_and_charsets() {
+        assert_eq!(lines("s[ae][@4]"), ["sa@", "sa4", "se@", "se4"]);
+        assert_eq!(lines("$[0-2]$[0-1]"), ["$0$0", "$0$1", "$1$0", "$1$1", "$2$0", "$2$1"]);
+        assert_eq!(lines("^?d").len(), 10);
+        assert_eq!(lines("^?u")[25], "^Z");
+        assert_eq!(lines("\\[ $??"), ["[ $?"]);
+        assert_eq!(lines("c $1"), ["c $1"]);
+    }
+
+    #[test]
+    fn number_ranges() {
+        assert_eq!(lines("$[1998-2001]"), ["$1$9$9$8", "$1$9$9$9", "$2$0$0$0", "$2$0$0$1"]);
+        // Prepending spells the number backwards, so it ends up in order
+        assert_eq!(lines("^[19-20]"), ["^9^1", "^0^2"]);
+        assert_eq!(lines("$[8-11]"), ["$8", "$9", "$1$0", "$1$1"]);
+    }
+
+    #[test]
+    fn zero_padded_number_ranges() {
+        let two_digits = lines("$[00-99]");
+        assert_eq!(two_digits.len(), 100);
+        assert_eq!(two_digits[0], "$0$0");
+        assert_eq!(two_digits[7], "$0$7");
+        assert_eq!(two_digits[99], "$9$9");
+        assert_eq!(lines("^[000-002]"), ["^0^0^0", "^1^0^0", "^2^0^0"]);
+    }
+
+    #[test]
+    fn invalid_templates() {
+        assert!(expand_line("$[9-0]", Dialect::Hashcat).is_err());
+        assert!(expand_line("$[20-10]", Dialect::Hashcat).is_err());
+        assert!(expand_line("s[ab", Dialect::Hashcat).is_err());
+        assert!(expand_line("c[10-20]", Dialect::Hashcat).is_err());
+        assert!(expand_line("$?b", Dialect::Hashcat).is_err());
+    }
+
+    #[test]
+    fn parallel_classes() {
+        let jtr = |t| expand_line(t, Dialect::Jtr);
+        assert_eq!(jtr("$[0-2]\\p0[a-c]").unwrap(), ["$0a", "$1b", "$2c"]);
+        assert_eq!(jtr("$[0-1]$[xy]\\p1[ab]").unwrap(), ["$0$xa", "$0$ya", "$1$xb", "$1$yb"]);
+        assert_eq!(jtr("^[0-1]^[xy]\\p[ab]").unwrap(), ["^0^xa", "^1^yb"]);
+        assert!(jtr("$[0-2]\\p0[ab]").is_err());
+        assert!(jtr("\\p[ab]").is_err());
+        assert!(jtr("$[ab]\\p2[ab]").is_err());
+        assert!(jtr("$[ab]\\r[ab]").is_err());
+    }
+
+    #[test]
+    fn expanded_counts() {
+        let (out, templates, lines) = expand("c\n$[0-9]\n# $[0-9]\n").unwrap();
+        assert_eq!((templates, lines), (1, 10));
+        assert_eq!(out.lines().count(), 12);
+    }
+}
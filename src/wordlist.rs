@@ -0,0 +1,171 @@
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Cursor, Read, Seek, SeekFrom};
+
+/// Generated input whose words can be computed from their index,
+/// such as the candidates of a mask.
+pub trait Generator {
+    /// Number of words
+    fn keyspace(&self) -> u64;
+    /// The word at `index`, `None` past the end
+    fn word(&self, index: u64) -> Option<String>;
+}
+
+enum Source {
+    Stdin(io::StdinLock<'static>),
+    File(BufReader<File>),
+    Memory(Cursor<Vec<u8>>),
+    /// The offset of a generated word is its index
+    Generated(Box<dyn Generator>),
+}
+
+/// Line based word reader which keeps track of its position in the input,
+/// so that a run can later be continued from the same word.
+pub struct Wordlist {
+    source: Source,
+    buf: Vec<u8>,
+    offset: u64,
+    index: u64,
+}
+
+impl Wordlist {
+    pub fn stdin() -> Wordlist {
+        Wordlist::new(Source::Stdin(io::stdin().lock()))
+    }
+
+    pub fn open(path: &str) -> io::Result<Wordlist> {
+        Ok(Wordlist::new(Source::File(BufReader::new(File::open(path)?))))
+    }
+
+    /// Uses the words of `generator` as input.
+    pub fn generated(generator: Box<dyn Generator>) -> Wordlist {
+        Wordlist::new(Source::Generated(generator))
+    }
+
+    /// Reads the words from `content`.
+    #[cfg(test)]
+    pub fn memory(content: &str) -> Wordlist {
+        Wordlist::new(Source::Memory(Cursor::new(content.as_bytes().to_vec())))
+    }
+
+    /// Makes the input re-readable, stdin is read into memory for that.
+    pub fn buffered(self) -> io::Result<Wordlist> {
+        match self.source {
+            Source::Stdin(mut r) => {
+                let mut content = Vec::new();
+                r.read_to_end(&mut content)?;
+                Ok(Wordlist::new(Source::Memory(Cursor::new(content))))
+            },
+            source => Ok(Wordlist::new(source))
+        }
+    }
+
+    fn new(source: Source) -> Wordlist {
+        Wordlist {
+            source,
+            buf: Vec::new(),
+            offset: 0,
+            index: 0
+        }
+    }
+
+    /// Byte offset of the next word in the input.
+    pub fn offset(&self) -> u64 {
+        self.offset
+    }
+
+    /// Index of the next word in the input.
+    pub fn index(&self) -> u64 {
+        self.index
+    }
+
+    /// Size of the input in bytes, unknown for stdin.
+    pub fn size(&self) -> Option<u64> {
+        match &self.source {
+            Source::File(r) => r.get_ref().metadata().ok().map(|m| m.len()),
+            Source::Memory(r) => Some(r.get_ref().len() as u64),
+            Source::Generated(g) => Some(g.keyspace()),
+            Source::Stdin(_) => None,
+        }
+    }
+
+    /// Reads the next raw line into the buffer, returns false at the end of the input.
+    fn read_line(&mut self) -> io::Result<bool> {
+        self.buf.clear();
+        let read = match &mut self.source {
+            Source::Stdin(r) => r.read_until(b'\n', &mut self.buf)?,
+            Source::File(r) => r.read_until(b'\n', &mut self.buf)?,
+            Source::Memory(r) => r.read_until(b'\n', &mut self.buf)?,
+            Source::Generated(g) => match g.word(self.index) {
+                Some(c) => {
+                    self.buf.extend_from_slice(c.as_bytes());
+                    1
+                },
+                None => 0
+            },
+        };
+        if read == 0 {
+            return Ok(false);
+        }
+        self.offset += read as u64;
+        self.index += 1;
+        Ok(true)
+    }
+
+    /// Skips up to `count` words without decoding them, returns how many were skipped.
+    pub fn skip_words(&mut self, count: u64) -> io::Result<u64> {
+        if let Source::Generated(g) = &self.source {
+            let skipped = count.min(g.keyspace().saturating_sub(self.index));
+            self.index += skipped;
+            self.offset += skipped;
+            return Ok(skipped);
+        }
+
+        let mut skipped = 0;
+        while skipped < count && self.read_line()? {
+            skipped += 1;
+        }
+        Ok(skipped)
+    }
+
+    /// Reads the next word, without its line ending.
+    pub fn next_word(&mut self) -> io::Result<Option<String>> {
+        if !self.read_line()? {
+            return Ok(None);
+        }
+
+        // Generated words have no line ending, a mask may end with a newline byte
+        if !matches!(self.source, Source::Generated(_)) && self.buf.last() == Some(&b'\n') {
+            self.buf.pop();
+            if self.buf.last() == Some(&b'\r') {
+                self.buf.pop();
+            }
+        }
+        Ok(Some(String::from_utf8_lossy(&self.buf).into_owned()))
+    }
+
+    /// Moves to the word at byte `offset`, which must be word number `index`.
+    /// Files are seeked directly, stdin has to be read up to that point.
+    pub fn seek(&mut self, offset: u64, index: u64) -> io::Result<()> {
+        match &mut self.source {
+            Source::File(r) => {
+                r.seek(SeekFrom::Start(offset))?;
+            },
+            Source::Memory(r) => {
+                r.seek(SeekFrom::Start(offset))?;
+            },
+            Source::Generated(_) => {},
+            Source::Stdin(r) => {
+                let skip = offset.checked_sub(self.offset).ok_or_else(|| {
+                    io::Error::new(io::ErrorKind::InvalidInput, "Cannot seek backwards in stdin")
+                })?;
+                let skipped = io::copy(&mut r.take(skip), &mut io::sink())?;
+                if skipped != skip {
+                    return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "Input is shorter than the restore point"));
+                }
+            }
+        }
+        self.offset = offset;
+        self.index = index;
+        Ok(())
+    }
+}